@@ -1,6 +1,16 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
-use switchboard_solana::AggregatorAccountData;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_spl::token_interface::spl_token_2022::extension::{
+    BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+};
+use anchor_spl::token_interface::{
+    self, spl_token_2022, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+use switchboard_on_demand::{PullFeedAccountData, SWITCHBOARD_ON_DEMAND_PROGRAM_ID};
+use switchboard_solana::{
+    AggregatorAccountData, AggregatorHistoryBuffer, AggregatorHistoryRow, SWITCHBOARD_PROGRAM_ID,
+};
 
 declare_id!("ArenaXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX");
 
@@ -11,11 +21,102 @@ pub const SWTCH_MINT: &str = "SW1TCH7qEPTdLsDHRgPuMQjbQxKdH2aBStViMFnt64f";
 /// Protocol fee in basis points (500 = 5%)
 pub const DEFAULT_PROTOCOL_FEE_BPS: u16 = 500;
 
-/// Minimum bet amount (1 $SWTCH with 6 decimals)
-pub const MIN_BET_AMOUNT: u64 = 1_000_000;
+/// Default minimum bet amount (1 $SWTCH with 6 decimals)
+pub const DEFAULT_MIN_BET_AMOUNT: u64 = 1_000_000;
 
-/// Maximum bet amount (10,000 $SWTCH with 6 decimals)
-pub const MAX_BET_AMOUNT: u64 = 10_000_000_000;
+/// Default maximum bet amount (10,000 $SWTCH with 6 decimals)
+pub const DEFAULT_MAX_BET_AMOUNT: u64 = 10_000_000_000;
+
+/// Maximum number of outcomes in a categorical market
+pub const MAX_OUTCOMES: usize = 8;
+
+/// Maximum length of a categorical outcome label
+pub const MAX_OUTCOME_LABEL_LEN: usize = 32;
+
+/// Default maximum age of an oracle round used by a market (5 minutes)
+pub const DEFAULT_MAX_STALENESS_SECS: i64 = 300;
+
+/// Default minimum number of oracles that must have responded
+pub const DEFAULT_MIN_ORACLE_RESULTS: u32 = 1;
+
+/// Default time after `resolution_time` to wait for an oracle round before
+/// voiding a market (1 hour)
+pub const DEFAULT_RESOLUTION_GRACE_SECS: i64 = 3_600;
+
+/// Fixed-point scale of Switchboard On-Demand results (18 decimals)
+pub const PULL_FEED_SCALE: i128 = 1_000_000_000_000_000_000;
+
+/// Maximum number of oracle samples kept for a settlement window
+pub const MAX_SETTLEMENT_SAMPLES: usize = 64;
+
+/// Denominator for the LONG share of a resolved scalar market
+pub const SCALAR_SHARE_PRECISION: u64 = 1_000_000_000;
+
+/// Default time after resolution during which the outcome can be challenged
+/// (24 hours)
+pub const DEFAULT_DISPUTE_PERIOD_SECS: i64 = 86_400;
+
+/// Longest dispute period the authority can configure (7 days)
+pub const MAX_DISPUTE_PERIOD_SECS: i64 = 604_800;
+
+/// Default bond posted to challenge a resolution (100 $SWTCH with 6 decimals)
+pub const DEFAULT_DISPUTE_BOND: u64 = 100_000_000;
+
+/// Default time after claims open before anyone can sweep an unclaimed
+/// position (90 days)
+pub const DEFAULT_CLAIM_EXPIRY_SECS: i64 = 7_776_000;
+
+/// Pause scopes for `ArenaState.paused`
+pub const PAUSE_DEPOSITS: u8 = 1 << 0;
+pub const PAUSE_WITHDRAWALS: u8 = 1 << 1;
+pub const PAUSE_BETTING: u8 = 1 << 2;
+pub const PAUSE_MARKET_CREATION: u8 = 1 << 3;
+/// Resolution, settlement sampling, disputes and finalization
+pub const PAUSE_RESOLUTION: u8 = 1 << 4;
+/// Claims and position sweeps
+pub const PAUSE_CLAIMS: u8 = 1 << 5;
+pub const PAUSE_ALL: u8 = PAUSE_DEPOSITS
+    | PAUSE_WITHDRAWALS
+    | PAUSE_BETTING
+    | PAUSE_MARKET_CREATION
+    | PAUSE_RESOLUTION
+    | PAUSE_CLAIMS;
+
+/// Roles granted through the `RoleRegistry`; the authority holds all of them
+pub const ROLE_MARKET_CREATOR: u8 = 1 << 0;
+/// Queue protocol and exit fee changes
+pub const ROLE_FEE_ADMIN: u8 = 1 << 1;
+pub const ROLE_PAUSER: u8 = 1 << 2;
+/// Settle disputes and cancel markets
+pub const ROLE_EMERGENCY_RESOLVER: u8 = 1 << 3;
+pub const ROLE_ALL: u8 =
+    ROLE_MARKET_CREATOR | ROLE_FEE_ADMIN | ROLE_PAUSER | ROLE_EMERGENCY_RESOLVER;
+
+/// Maximum number of keys holding roles
+pub const MAX_ROLE_MEMBERS: usize = 16;
+
+/// Maximum number of admin council members
+pub const MAX_COUNCIL_MEMBERS: usize = 10;
+
+/// Limits on the arena instruction a council action can carry
+pub const MAX_COUNCIL_ACTION_ACCOUNTS: usize = 16;
+pub const MAX_COUNCIL_ACTION_DATA_LEN: usize = 256;
+
+/// Default $SWTCH bond posted by community market creators (100 $SWTCH)
+pub const DEFAULT_CREATION_BOND: u64 = 100_000_000;
+
+/// Maximum share of a market's protocol fee paid to its creator (50%)
+pub const MAX_CREATOR_FEE_BPS: u16 = 5_000;
+
+/// Maximum share of the protocol fee rebated to referrers (50%)
+pub const MAX_REFERRAL_FEE_BPS: u16 = 5_000;
+
+/// Default delay before a queued admin change can be executed (2 days)
+pub const DEFAULT_TIMELOCK_DELAY_SECS: i64 = 172_800;
+
+/// Bounds on the timelock delay (1 hour to 30 days)
+pub const MIN_TIMELOCK_DELAY_SECS: i64 = 3_600;
+pub const MAX_TIMELOCK_DELAY_SECS: i64 = 2_592_000;
 
 #[program]
 pub mod feedgod_arena {
@@ -31,16 +132,53 @@ pub mod feedgod_arena {
             protocol_fee_bps <= 1000, // Max 10%
             ArenaError::InvalidFeePercentage
         );
+        check_mint_extensions(&ctx.accounts.swtch_mint.to_account_info())?;
 
         let arena_state = &mut ctx.accounts.arena_state;
         arena_state.authority = ctx.accounts.authority.key();
+        arena_state.pending_authority = Pubkey::default();
         arena_state.treasury = ctx.accounts.treasury.key();
+        arena_state.pending_treasury = Pubkey::default();
         arena_state.swtch_mint = ctx.accounts.swtch_mint.key();
-        arena_state.total_volume = 0;
         arena_state.total_markets = 0;
         arena_state.protocol_fee_bps = protocol_fee_bps;
+        arena_state.exit_fee_bps = 0;
+        arena_state.oracle_policy = OraclePolicy {
+            max_staleness_secs: DEFAULT_MAX_STALENESS_SECS,
+            max_std_deviation: 0,
+            min_oracle_results: DEFAULT_MIN_ORACLE_RESULTS,
+            resolution_grace_secs: DEFAULT_RESOLUTION_GRACE_SECS,
+        };
+        arena_state.dispute_period_secs = DEFAULT_DISPUTE_PERIOD_SECS;
+        arena_state.dispute_bond = DEFAULT_DISPUTE_BOND;
+        arena_state.claim_expiry_secs = DEFAULT_CLAIM_EXPIRY_SECS;
+        arena_state.bet_limits = BetLimits {
+            min_bet_amount: DEFAULT_MIN_BET_AMOUNT,
+            max_bet_amount: DEFAULT_MAX_BET_AMOUNT,
+            max_position_amount: 0,
+        };
+        arena_state.max_open_exposure = 0;
+        arena_state.open_creation = false;
+        arena_state.creation_bond = DEFAULT_CREATION_BOND;
+        arena_state.creator_fee_bps = 0;
+        arena_state.referral_fee_bps = 0;
+        arena_state.timelock_delay_secs = DEFAULT_TIMELOCK_DELAY_SECS;
+        arena_state.total_changes = 0;
+        arena_state.paused = 0;
         arena_state.bump = ctx.bumps.arena_state;
 
+        let role_registry = &mut ctx.accounts.role_registry;
+        role_registry.members = Vec::new();
+        role_registry.bump = ctx.bumps.role_registry;
+
+        // $SWTCH is always accepted as collateral
+        let collateral = &mut ctx.accounts.swtch_collateral;
+        collateral.mint = ctx.accounts.swtch_mint.key();
+        collateral.enabled = true;
+        collateral.total_volume = 0;
+        collateral.accrued_fees = 0;
+        collateral.bump = ctx.bumps.swtch_collateral;
+
         emit!(ArenaInitialized {
             authority: arena_state.authority,
             treasury: arena_state.treasury,
@@ -50,29 +188,57 @@ pub mod feedgod_arena {
         Ok(())
     }
 
-    /// User deposits $SWTCH tokens into their arena account
+    /// User deposits collateral tokens into their arena account for that mint
     pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
         require!(amount > 0, ArenaError::InvalidAmount);
 
         // Transfer tokens from user to vault
-        let cpi_accounts = Transfer {
+        let vault_before = ctx.accounts.vault.amount;
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.user_token_account.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
             to: ctx.accounts.vault.to_account_info(),
             authority: ctx.accounts.user.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, amount)?;
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        // Credit what actually arrived, net of any Token-2022 transfer fee
+        ctx.accounts.vault.reload()?;
+        let amount = ctx.accounts.vault.amount.checked_sub(vault_before)
+            .ok_or(ArenaError::Underflow)?;
+        require!(amount > 0, ArenaError::InvalidAmount);
+
+        // A referrer can only be named by the user's first deposit, in any
+        // mint, however their `Referral` was created
+        let referral = &mut ctx.accounts.referral;
+        referral.init_if_new(ctx.accounts.user.key(), ctx.bumps.referral);
+        if !referral.deposited {
+            referral.deposited = true;
+            if let Some(referrer_referral) = ctx.accounts.referrer_referral.as_mut() {
+                referral.referrer = referrer_referral.user;
+                referrer_referral.referral_count = referrer_referral.referral_count.checked_add(1)
+                    .ok_or(ArenaError::Overflow)?;
+
+                emit!(ReferralRegistered {
+                    user: referral.user,
+                    referrer: referral.referrer,
+                });
+            }
+        }
 
         // Update user account
         let user_account = &mut ctx.accounts.user_account;
         user_account.user = ctx.accounts.user.key();
+        user_account.mint = ctx.accounts.mint.key();
         user_account.balance = user_account.balance.checked_add(amount)
             .ok_or(ArenaError::Overflow)?;
         user_account.bump = ctx.bumps.user_account;
 
         emit!(Deposited {
             user: ctx.accounts.user.key(),
+            mint: user_account.mint,
             amount,
             new_balance: user_account.balance,
         });
@@ -80,7 +246,7 @@ pub mod feedgod_arena {
         Ok(())
     }
 
-    /// User withdraws $SWTCH tokens from their arena account
+    /// User withdraws collateral tokens from their arena account for that mint
     pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
         require!(amount > 0, ArenaError::InvalidAmount);
         
@@ -102,17 +268,20 @@ pub mod feedgod_arena {
         ];
         let signer_seeds = &[&seeds[..]];
 
-        let cpi_accounts = Transfer {
+        // Any transfer fee is withheld from what the user receives
+        let cpi_accounts = TransferChecked {
             from: ctx.accounts.vault.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
             to: ctx.accounts.user_token_account.to_account_info(),
             authority: ctx.accounts.arena_state.to_account_info(),
         };
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-        token::transfer(cpi_ctx, amount)?;
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
 
         emit!(Withdrawn {
             user: ctx.accounts.user.key(),
+            mint: user_account.mint,
             amount,
             new_balance: user_account.balance,
         });
@@ -121,14 +290,26 @@ pub mod feedgod_arena {
     }
 
     /// Create a new prediction market
-    /// Only authority can create markets
+    /// The authority and market creators list markets freely; with open
+    /// creation anyone else can, by posting `creation_bond` in $SWTCH
     pub fn create_market(
         ctx: Context<CreateMarket>,
-        oracle_feed: Pubkey,
-        description: String,
-        category: String,
-        resolution_time: i64,
+        params: CreateMarketParams,
     ) -> Result<()> {
+        let CreateMarketParams {
+            oracle_feed,
+            description,
+            category,
+            lock_time,
+            resolution_time,
+            kind,
+            void_on_tie,
+            oracle_kind,
+            oracle_policy,
+            bet_limits,
+            settlement,
+        } = params;
+
         require!(
             description.len() <= 200,
             ArenaError::DescriptionTooLong
@@ -143,37 +324,183 @@ pub mod feedgod_arena {
             resolution_time > clock.unix_timestamp,
             ArenaError::InvalidResolutionTime
         );
+        require!(
+            lock_time > clock.unix_timestamp && lock_time <= resolution_time,
+            ArenaError::InvalidLockTime
+        );
+
+        let mut oracle_policy = oracle_policy.unwrap_or(ctx.accounts.arena_state.oracle_policy);
+        // An approved feed can demand more responding oracles than the policy
+        let approved_feed = &ctx.accounts.approved_feed;
+        oracle_policy.min_oracle_results = oracle_policy.min_oracle_results
+            .max(approved_feed.min_oracle_results);
+        oracle_policy.validate()?;
+        let bet_limits = bet_limits.unwrap_or(ctx.accounts.arena_state.bet_limits);
+        bet_limits.validate()?;
+        settlement.validate(clock.unix_timestamp, resolution_time)?;
+        // Pull feeds keep no history, so a spot read would depend on when
+        // resolution is cranked; they settle over a window instead
+        require!(
+            oracle_kind == OracleKind::AggregatorV2 || settlement.mode != SettlementMode::Spot,
+            ArenaError::InvalidSettlementConfig
+        );
 
         // Read current value from oracle
-        let feed = &ctx.accounts.oracle_feed;
-        let feed_data = feed.load()?;
-        let start_value = feed_data.get_result()?.try_into()?;
+        let reading = OracleReading::load(&ctx.accounts.oracle_feed, oracle_kind)?;
+        approved_feed.check(oracle_kind, &reading)?;
+        let start_value = reading.checked_value(&oracle_policy, clock.unix_timestamp)?;
+
+        // Spot reads are looked up in the history buffer at resolution
+        if settlement.mode == SettlementMode::Spot {
+            require!(
+                reading.history_buffer != Pubkey::default(),
+                ArenaError::InvalidHistoryBuffer
+            );
+        }
+
+        // Community creators bond their listing and earn a share of its fee
+        let creator = ctx.accounts.creator.key();
+        let arena_state = &ctx.accounts.arena_state;
+        let trusted = arena_state.authority == creator
+            || ctx.accounts.role_registry.has_role(&creator, ROLE_MARKET_CREATOR);
+        let (creation_bond, creator_fee_bps) = if trusted {
+            (0, 0)
+        } else {
+            (arena_state.creation_bond, arena_state.creator_fee_bps)
+        };
+        if creation_bond > 0 {
+            let creator_account = ctx.accounts.creator_account.as_mut()
+                .ok_or(ArenaError::CreatorAccountRequired)?;
+            require!(
+                creator_account.balance >= creation_bond,
+                ArenaError::InsufficientBalance
+            );
+            creator_account.balance = creator_account.balance.checked_sub(creation_bond)
+                .ok_or(ArenaError::Underflow)?;
+        }
 
         let arena_state = &mut ctx.accounts.arena_state;
         let market_id = arena_state.total_markets;
         arena_state.total_markets = arena_state.total_markets.checked_add(1)
             .ok_or(ArenaError::Overflow)?;
 
+        // Window markets get their sample buffer up front, so resolution can
+        // always count what was collected
+        require!(
+            (settlement.mode == SettlementMode::Spot) == ctx.accounts.samples.is_none(),
+            ArenaError::InvalidSamplesAccount
+        );
+        let market_key = ctx.accounts.market.key();
+        if let Some(samples) = ctx.accounts.samples.as_mut() {
+            samples.market = market_key;
+            samples.samples = Vec::new();
+            samples.bump = ctx.bumps.samples;
+        }
+
         let market = &mut ctx.accounts.market;
         market.id = market_id;
+        market.creator = creator;
+        market.collateral_mint = ctx.accounts.collateral.mint;
         market.oracle_feed = oracle_feed;
+        market.oracle_kind = oracle_kind;
         market.description = description.clone();
         market.category = category.clone();
         market.start_value = start_value;
+        market.strike_value = start_value;
+        market.threshold_direction = ThresholdDirection::Above;
+        market.void_on_tie = void_on_tie;
+        market.oracle_policy = oracle_policy;
+        market.bet_limits = bet_limits;
+        market.settlement = settlement;
+        market.lock_time = lock_time;
         market.resolution_time = resolution_time;
         market.total_up_pool = 0;
         market.total_down_pool = 0;
         market.resolved = false;
         market.outcome = None;
+        market.fee_collected = 0;
+        market.creation_bond = creation_bond;
+        market.creator_fee_bps = creator_fee_bps;
+        market.creator_fee = 0;
+        market.referral_fee_bps = ctx.accounts.arena_state.referral_fee_bps;
+        market.referral_reserve = 0;
+        market.referral_paid = 0;
+        market.status = MarketStatus::Open;
+        market.dispute_deadline = 0;
+        market.challenger = Pubkey::default();
+        market.challenge_bond = 0;
+        market.proposed_outcome = None;
+        market.cancel_reason = None;
+        market.open_positions = 0;
         market.bump = ctx.bumps.market;
 
+        match kind {
+            MarketKind::UpDown => {
+                market.market_type = MarketType::UpDown;
+            }
+            MarketKind::Threshold { strike_value, direction } => {
+                market.market_type = MarketType::Threshold;
+                market.strike_value = strike_value;
+                market.threshold_direction = direction;
+            }
+            MarketKind::Categorical { labels, resolution, range_bounds } => {
+                require!(
+                    labels.len() >= 2 && labels.len() <= MAX_OUTCOMES,
+                    ArenaError::InvalidOutcomeCount
+                );
+                require!(
+                    labels.iter().all(|label| !label.is_empty() && label.len() <= MAX_OUTCOME_LABEL_LEN),
+                    ArenaError::InvalidOutcomeLabel
+                );
+                match resolution {
+                    CategoricalResolution::Index => {
+                        require!(range_bounds.is_empty(), ArenaError::InvalidOutcomeRanges);
+                    }
+                    CategoricalResolution::Ranges => {
+                        // One boundary between each pair of adjacent outcomes
+                        require!(
+                            range_bounds.len() == labels.len() - 1
+                                && range_bounds.windows(2).all(|w| w[0] < w[1]),
+                            ArenaError::InvalidOutcomeRanges
+                        );
+                    }
+                }
+
+                market.market_type = MarketType::Categorical;
+                market.outcome_pools = vec![0; labels.len()];
+                market.outcome_labels = labels;
+                market.categorical_resolution = resolution;
+                market.range_bounds = range_bounds;
+            }
+            MarketKind::Scalar { lower_bound, upper_bound } => {
+                require!(lower_bound < upper_bound, ArenaError::InvalidScalarBounds);
+                require!(
+                    upper_bound.checked_sub(lower_bound).is_some(),
+                    ArenaError::InvalidScalarBounds
+                );
+
+                market.market_type = MarketType::Scalar;
+                market.lower_bound = lower_bound;
+                market.upper_bound = upper_bound;
+            }
+        }
+
         emit!(MarketCreated {
             market_id,
+            collateral_mint: market.collateral_mint,
             oracle_feed,
+            oracle_kind,
             description,
             category,
+            market_type: market.market_type,
             start_value,
+            strike_value: market.strike_value,
+            threshold_direction: market.threshold_direction,
+            outcome_labels: market.outcome_labels.clone(),
+            lock_time,
             resolution_time,
+            creator,
+            creation_bond,
         });
 
         Ok(())
@@ -185,36 +512,29 @@ pub mod feedgod_arena {
         prediction: bool, // true = UP, false = DOWN
         amount: u64,
     ) -> Result<()> {
-        require!(
-            amount >= MIN_BET_AMOUNT,
-            ArenaError::BetTooSmall
-        );
-        require!(
-            amount <= MAX_BET_AMOUNT,
-            ArenaError::BetTooLarge
-        );
-
         let market = &ctx.accounts.market;
+        market.bet_limits.check_bet(amount)?;
+        require!(market.status != MarketStatus::Cancelled, ArenaError::MarketCancelled);
         require!(!market.resolved, ArenaError::MarketAlreadyResolved);
-
-        let clock = Clock::get()?;
         require!(
-            clock.unix_timestamp < market.resolution_time,
-            ArenaError::BettingClosed
+            market.market_type != MarketType::Categorical,
+            ArenaError::InvalidMarketType
         );
 
-        // Check user has enough balance
-        let user_account = &mut ctx.accounts.user_account;
+        let clock = Clock::get()?;
         require!(
-            user_account.balance >= amount,
-            ArenaError::InsufficientBalance
+            market.status == MarketStatus::Open && clock.unix_timestamp < market.lock_time,
+            ArenaError::BettingClosed
         );
 
-        // Deduct from user balance
-        user_account.balance = user_account.balance.checked_sub(amount)
-            .ok_or(ArenaError::Underflow)?;
-        user_account.total_wagered = user_account.total_wagered.checked_add(amount)
-            .ok_or(ArenaError::Overflow)?;
+        debit_wager(&mut ctx.accounts.user_account, amount)?;
+        add_exposure(
+            &mut ctx.accounts.user_account,
+            amount,
+            ctx.accounts.market.resolution_time,
+            clock.unix_timestamp,
+            ctx.accounts.arena_state.max_open_exposure,
+        )?;
 
         // Update market pools
         let market = &mut ctx.accounts.market;
@@ -226,19 +546,23 @@ pub mod feedgod_arena {
                 .ok_or(ArenaError::Overflow)?;
         }
 
-        // Update arena volume
-        let arena_state = &mut ctx.accounts.arena_state;
-        arena_state.total_volume = arena_state.total_volume.checked_add(amount)
+        // Update collateral volume
+        let collateral = &mut ctx.accounts.collateral;
+        collateral.total_volume = collateral.total_volume.checked_add(amount)
             .ok_or(ArenaError::Overflow)?;
 
-        // Create position
+        // Create the position on the first bet, then add to the chosen side
         let position = &mut ctx.accounts.position;
-        position.user = ctx.accounts.user.key();
-        position.market = market.key();
-        position.prediction = prediction;
-        position.amount = amount;
-        position.claimed = false;
-        position.bump = ctx.bumps.position;
+        if position.market == Pubkey::default() {
+            position.user = ctx.accounts.user.key();
+            position.market = market.key();
+            position.bump = ctx.bumps.position;
+            market.open_positions = market.open_positions.checked_add(1)
+                .ok_or(ArenaError::Overflow)?;
+        }
+        let side = if prediction { BetSide::Up } else { BetSide::Down };
+        position.add_stake(side, amount)?;
+        market.bet_limits.check_position(position.amount)?;
 
         emit!(BetPlaced {
             user: ctx.accounts.user.key(),
@@ -252,11 +576,166 @@ pub mod feedgod_arena {
         Ok(())
     }
 
+    /// Place a bet on one outcome of a categorical market
+    pub fn place_outcome_bet(
+        ctx: Context<PlaceBet>,
+        outcome_index: u8,
+        amount: u64,
+    ) -> Result<()> {
+        let market = &ctx.accounts.market;
+        market.bet_limits.check_bet(amount)?;
+        require!(market.status != MarketStatus::Cancelled, ArenaError::MarketCancelled);
+        require!(!market.resolved, ArenaError::MarketAlreadyResolved);
+        require!(
+            market.market_type == MarketType::Categorical,
+            ArenaError::InvalidMarketType
+        );
+        require!(
+            (outcome_index as usize) < market.outcome_pools.len(),
+            ArenaError::InvalidOutcomeIndex
+        );
+
+        let clock = Clock::get()?;
+        require!(
+            market.status == MarketStatus::Open && clock.unix_timestamp < market.lock_time,
+            ArenaError::BettingClosed
+        );
+
+        debit_wager(&mut ctx.accounts.user_account, amount)?;
+        add_exposure(
+            &mut ctx.accounts.user_account,
+            amount,
+            ctx.accounts.market.resolution_time,
+            clock.unix_timestamp,
+            ctx.accounts.arena_state.max_open_exposure,
+        )?;
+
+        // Update outcome pool
+        let market = &mut ctx.accounts.market;
+        let pool = &mut market.outcome_pools[outcome_index as usize];
+        *pool = pool.checked_add(amount)
+            .ok_or(ArenaError::Overflow)?;
+        let outcome_pool = *pool;
+
+        // Update collateral volume
+        let collateral = &mut ctx.accounts.collateral;
+        collateral.total_volume = collateral.total_volume.checked_add(amount)
+            .ok_or(ArenaError::Overflow)?;
+
+        // Create the position on the first bet, then add to the outcome
+        let position = &mut ctx.accounts.position;
+        if position.market == Pubkey::default() {
+            position.user = ctx.accounts.user.key();
+            position.market = market.key();
+            position.bump = ctx.bumps.position;
+            market.open_positions = market.open_positions.checked_add(1)
+                .ok_or(ArenaError::Overflow)?;
+        }
+        position.add_stake(BetSide::Outcome { index: outcome_index }, amount)?;
+        market.bet_limits.check_position(position.amount)?;
+
+        emit!(OutcomeBetPlaced {
+            user: ctx.accounts.user.key(),
+            market: market.key(),
+            outcome_index,
+            amount,
+            outcome_pool,
+        });
+
+        Ok(())
+    }
+
+    /// Take part of a stake back out of a market before betting closes
+    /// An exit fee of `ArenaState.exit_fee_bps` is kept as protocol fees
+    pub fn reduce_position(
+        ctx: Context<ReducePosition>,
+        side: BetSide,
+        amount: u64,
+    ) -> Result<()> {
+        require!(amount > 0, ArenaError::InvalidAmount);
+
+        let market = &mut ctx.accounts.market;
+        require!(market.status != MarketStatus::Cancelled, ArenaError::MarketCancelled);
+        require!(!market.resolved, ArenaError::MarketAlreadyResolved);
+
+        let clock = Clock::get()?;
+        require!(
+            market.status == MarketStatus::Open && clock.unix_timestamp < market.lock_time,
+            ArenaError::BettingClosed
+        );
+
+        // Whatever is left on the side must still be a valid bet
+        let position = &mut ctx.accounts.position;
+        let remaining = position.stake(side)
+            .checked_sub(amount)
+            .ok_or(ArenaError::InsufficientStake)?;
+        require!(
+            remaining == 0 || remaining >= market.bet_limits.min_bet_amount,
+            ArenaError::BetTooSmall
+        );
+
+        let exit_fee = protocol_fee(amount, ctx.accounts.arena_state.exit_fee_bps)?;
+
+        // Update pools, position and volume as if the stake was never bet
+        let pool = market.side_pool_mut(side)?;
+        *pool = pool.checked_sub(amount)
+            .ok_or(ArenaError::Underflow)?;
+        position.remove_stake(side, amount)?;
+        let collateral = &mut ctx.accounts.collateral;
+        collateral.total_volume = collateral.total_volume.checked_sub(amount)
+            .ok_or(ArenaError::Underflow)?;
+        collateral.accrued_fees = collateral.accrued_fees.checked_add(exit_fee)
+            .ok_or(ArenaError::Overflow)?;
+
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.total_wagered = user_account.total_wagered.checked_sub(amount)
+            .ok_or(ArenaError::Underflow)?;
+        release_exposure(user_account, market.resolution_time, amount)?;
+        user_account.balance = user_account.balance
+            .checked_add(amount - exit_fee)
+            .ok_or(ArenaError::Overflow)?;
+
+        emit!(PositionReduced {
+            user: ctx.accounts.user.key(),
+            market: market.key(),
+            side,
+            amount,
+            exit_fee,
+            remaining,
+        });
+
+        Ok(())
+    }
+
+    /// Close betting on a market once its lock time has passed
+    /// Can be called by anyone
+    pub fn lock_market(ctx: Context<LockMarket>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        require!(market.status == MarketStatus::Open, ArenaError::MarketNotOpen);
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= market.lock_time,
+            ArenaError::LockTimeNotReached
+        );
+
+        market.status = MarketStatus::Locked;
+
+        emit!(MarketLocked {
+            market: market.key(),
+            lock_time: market.lock_time,
+            total_pool: market.total_pool()?,
+        });
+
+        Ok(())
+    }
+
     /// Resolve a market by reading the oracle
     /// Can be called by anyone after resolution time
     pub fn resolve_market(ctx: Context<ResolveMarket>) -> Result<()> {
         let market = &mut ctx.accounts.market;
         
+        require!(market.status != MarketStatus::Cancelled, ArenaError::MarketCancelled);
         require!(!market.resolved, ArenaError::MarketAlreadyResolved);
 
         let clock = Clock::get()?;
@@ -265,584 +744,4069 @@ pub mod feedgod_arena {
             ArenaError::ResolutionTimeNotReached
         );
 
-        // Read current value from oracle
-        let feed = &ctx.accounts.oracle_feed;
-        let feed_data = feed.load()?;
-        let current_value: i128 = feed_data.get_result()?.try_into()?;
+        // Past this point a market with no usable reading is voided
+        let deadline = market.resolution_time
+            .checked_add(market.oracle_policy.resolution_grace_secs)
+            .ok_or(ArenaError::Overflow)?;
+
+        let settled_value = match market.settlement.mode {
+            // Use the first oracle round at or after resolution time, so the
+            // outcome does not depend on when this is called
+            SettlementMode::Spot => OracleReading::load_as_of(
+                &ctx.accounts.oracle_feed,
+                ctx.accounts.history_buffer.as_ref().map(|h| h.as_ref()),
+                &market.oracle_policy,
+                market.resolution_time,
+            )?
+            .filter(|sample| sample.timestamp <= deadline)
+            .map(|sample| sample.value),
+            // Settle on the samples cranked in during the settlement window;
+            // the buffer exists from creation, so it must be passed
+            mode => {
+                let samples = ctx.accounts.samples.as_ref()
+                    .ok_or(ArenaError::InsufficientSamples)?;
+                if samples.samples.len() >= market.settlement.min_samples as usize {
+                    Some(if mode == SettlementMode::Twap {
+                        samples.time_weighted_average(market.resolution_time)?
+                    } else {
+                        samples.median()
+                    })
+                } else {
+                    None
+                }
+            }
+        };
+
+        let (end_value, outcome) = match settled_value {
+            Some(value) => (value, market.outcome_for(value)?),
+            None => {
+                require!(
+                    clock.unix_timestamp > deadline,
+                    if market.settlement.mode == SettlementMode::Spot {
+                        ArenaError::OracleRoundNotAvailable
+                    } else {
+                        ArenaError::InsufficientSamples
+                    }
+                );
+                (0, MarketOutcome::Void)
+            }
+        };
+        let total_pool = market.total_pool()?;
+
+        // The protocol fee is set aside now but only escrowed once the
+        // outcome is final; void markets refund stakes in full
+        let arena_state = &ctx.accounts.arena_state;
+        let fee_collected = if outcome == MarketOutcome::Void {
+            0
+        } else {
+            protocol_fee(total_pool, arena_state.protocol_fee_bps)?
+        };
+        let dispute_deadline = clock.unix_timestamp
+            .checked_add(arena_state.dispute_period_secs)
+            .ok_or(ArenaError::Overflow)?;
 
-        // Determine outcome: UP wins if value increased
-        let outcome = current_value > market.start_value;
-        
         market.resolved = true;
+        market.end_value = end_value;
         market.outcome = Some(outcome);
+        market.fee_collected = fee_collected;
+        market.status = MarketStatus::Resolved;
+        market.dispute_deadline = dispute_deadline;
 
         emit!(MarketResolved {
             market: market.key(),
             start_value: market.start_value,
-            end_value: current_value,
+            end_value,
             outcome,
-            total_pool: market.total_up_pool + market.total_down_pool,
+            total_pool,
+            fee_collected,
+            dispute_deadline,
         });
 
         Ok(())
     }
 
-    /// Claim winnings from a resolved market
-    pub fn claim_winnings(ctx: Context<ClaimWinnings>) -> Result<()> {
+    /// Record an oracle reading for a market's settlement window
+    /// Can be called by anyone during the last `window_secs` before resolution
+    pub fn record_sample(ctx: Context<RecordSample>) -> Result<()> {
         let market = &ctx.accounts.market;
-        let position = &mut ctx.accounts.position;
-        
-        require!(market.resolved, ArenaError::MarketNotResolved);
-        require!(!position.claimed, ArenaError::AlreadyClaimed);
-        
-        let outcome = market.outcome.ok_or(ArenaError::MarketNotResolved)?;
-        
-        // Check if user won
-        let user_won = position.prediction == outcome;
-        
-        if user_won {
-            let total_pool = market.total_up_pool
-                .checked_add(market.total_down_pool)
-                .ok_or(ArenaError::Overflow)?;
-            
-            let winning_pool = if outcome {
-                market.total_up_pool
-            } else {
-                market.total_down_pool
-            };
+        require!(market.status != MarketStatus::Cancelled, ArenaError::MarketCancelled);
+        require!(!market.resolved, ArenaError::MarketAlreadyResolved);
+        require!(
+            market.settlement.mode != SettlementMode::Spot,
+            ArenaError::SamplingNotEnabled
+        );
 
-            // Calculate protocol fee (5%)
-            let arena_state = &ctx.accounts.arena_state;
-            let fee_amount = total_pool
-                .checked_mul(arena_state.protocol_fee_bps as u64)
-                .ok_or(ArenaError::Overflow)?
-                .checked_div(10_000)
-                .ok_or(ArenaError::DivisionByZero)?;
-            
-            let pool_after_fee = total_pool
-                .checked_sub(fee_amount)
-                .ok_or(ArenaError::Underflow)?;
+        let clock = Clock::get()?;
+        let window_start = market.resolution_time
+            .checked_sub(market.settlement.window_secs)
+            .ok_or(ArenaError::Underflow)?;
+        require!(
+            clock.unix_timestamp >= window_start
+                && clock.unix_timestamp < market.resolution_time,
+            ArenaError::OutsideSettlementWindow
+        );
 
-            // Calculate user's share
-            // payout = (user_wager / winning_pool) * pool_after_fee
-            let user_payout = (position.amount as u128)
-                .checked_mul(pool_after_fee as u128)
-                .ok_or(ArenaError::Overflow)?
-                .checked_div(winning_pool as u128)
-                .ok_or(ArenaError::DivisionByZero)? as u64;
-
-            // Transfer fee to treasury
-            let seeds = &[
-                b"arena_state".as_ref(),
-                &[arena_state.bump],
-            ];
-            let signer_seeds = &[&seeds[..]];
-
-            if fee_amount > 0 {
-                let cpi_accounts = Transfer {
-                    from: ctx.accounts.vault.to_account_info(),
-                    to: ctx.accounts.treasury.to_account_info(),
-                    authority: ctx.accounts.arena_state.to_account_info(),
-                };
-                let cpi_program = ctx.accounts.token_program.to_account_info();
-                let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
-                token::transfer(cpi_ctx, fee_amount)?;
-            }
+        let reading = OracleReading::load(&ctx.accounts.oracle_feed, market.oracle_kind)?;
+        let value = reading.checked_value(&market.oracle_policy, clock.unix_timestamp)?;
 
-            // Credit user account
-            let user_account = &mut ctx.accounts.user_account;
-            user_account.balance = user_account.balance.checked_add(user_payout)
-                .ok_or(ArenaError::Overflow)?;
-            user_account.total_won = user_account.total_won.checked_add(user_payout)
-                .ok_or(ArenaError::Overflow)?;
-            user_account.wins = user_account.wins.checked_add(1)
-                .ok_or(ArenaError::Overflow)?;
-            user_account.current_streak = user_account.current_streak.checked_add(1)
-                .ok_or(ArenaError::Overflow)?;
-            if user_account.current_streak > user_account.best_streak {
-                user_account.best_streak = user_account.current_streak;
-            }
+        let samples = &mut ctx.accounts.samples;
 
-            emit!(WinningsClaimed {
-                user: ctx.accounts.user.key(),
-                market: market.key(),
-                payout: user_payout,
-                fee: fee_amount,
-            });
-        } else {
-            // User lost - update stats
-            let user_account = &mut ctx.accounts.user_account;
-            user_account.losses = user_account.losses.checked_add(1)
-                .ok_or(ArenaError::Overflow)?;
-            user_account.current_streak = 0;
+        // Only fresh oracle rounds from inside the window count
+        let last_timestamp = samples.samples.last().map_or(window_start - 1, |s| s.timestamp);
+        require!(
+            reading.timestamp >= window_start && reading.timestamp > last_timestamp,
+            ArenaError::DuplicateSample
+        );
+        require!(
+            samples.samples.len() < MAX_SETTLEMENT_SAMPLES,
+            ArenaError::SampleBufferFull
+        );
 
-            emit!(BetLost {
-                user: ctx.accounts.user.key(),
-                market: market.key(),
-                amount_lost: position.amount,
-            });
-        }
+        samples.samples.push(OracleSample {
+            timestamp: reading.timestamp,
+            value,
+        });
 
-        position.claimed = true;
+        emit!(SampleRecorded {
+            market: market.key(),
+            timestamp: reading.timestamp,
+            value,
+            sample_count: samples.samples.len() as u16,
+        });
 
         Ok(())
     }
 
-    /// Update protocol fee (admin only)
-    pub fn update_fee(ctx: Context<UpdateFee>, new_fee_bps: u16) -> Result<()> {
+    /// Challenge a resolution during the dispute period by posting a bond
+    /// from the challenger's arena balance
+    pub fn challenge_resolution(
+        ctx: Context<ChallengeResolution>,
+        proposed_outcome: MarketOutcome,
+    ) -> Result<()> {
+        let market = &mut ctx.accounts.market;
         require!(
-            new_fee_bps <= 1000, // Max 10%
-            ArenaError::InvalidFeePercentage
+            market.status == MarketStatus::Resolved,
+            ArenaError::MarketNotChallengeable
         );
 
-        let arena_state = &mut ctx.accounts.arena_state;
-        let old_fee = arena_state.protocol_fee_bps;
-        arena_state.protocol_fee_bps = new_fee_bps;
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp < market.dispute_deadline,
+            ArenaError::DisputePeriodEnded
+        );
+        market.check_outcome(proposed_outcome)?;
+        require!(
+            market.outcome != Some(proposed_outcome),
+            ArenaError::InvalidOutcome
+        );
+
+        let bond = ctx.accounts.arena_state.dispute_bond;
+        let user_account = &mut ctx.accounts.user_account;
+        require!(
+            user_account.balance >= bond,
+            ArenaError::InsufficientBalance
+        );
+        user_account.balance = user_account.balance.checked_sub(bond)
+            .ok_or(ArenaError::Underflow)?;
 
-        emit!(FeeUpdated {
-            old_fee_bps: old_fee,
-            new_fee_bps,
+        market.status = MarketStatus::Disputed;
+        market.challenger = ctx.accounts.challenger.key();
+        market.challenge_bond = bond;
+        market.proposed_outcome = Some(proposed_outcome);
+
+        emit!(MarketChallenged {
+            market: market.key(),
+            challenger: market.challenger,
+            proposed_outcome,
+            bond,
         });
 
         Ok(())
     }
 
-    /// Transfer authority (admin only)
-    pub fn transfer_authority(ctx: Context<TransferAuthority>, new_authority: Pubkey) -> Result<()> {
-        let arena_state = &mut ctx.accounts.arena_state;
-        let old_authority = arena_state.authority;
-        arena_state.authority = new_authority;
+    /// Settle a challenged market (authority or emergency resolver only)
+    /// The bond is refunded if the outcome is overturned and slashed to the
+    /// treasury if it stands
+    pub fn settle_dispute(ctx: Context<SettleDispute>, outcome: MarketOutcome) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        require!(
+            market.status == MarketStatus::Disputed,
+            ArenaError::MarketNotDisputed
+        );
+        market.check_outcome(outcome)?;
+        let outcome = market.refund_if_unbacked(outcome);
 
-        emit!(AuthorityTransferred {
-            old_authority,
-            new_authority,
+        let original = market.outcome.ok_or(ArenaError::MarketNotResolved)?;
+        let upheld = outcome == original;
+        let bond = market.challenge_bond;
+
+        let arena_state = &ctx.accounts.arena_state;
+        let fee_collected = match outcome {
+            MarketOutcome::Void => 0,
+            _ if original == MarketOutcome::Void => {
+                protocol_fee(market.total_pool()?, arena_state.protocol_fee_bps)?
+            }
+            _ => market.fee_collected,
+        };
+
+        escrow_fee(market, &mut ctx.accounts.collateral, fee_collected)?;
+
+        if upheld {
+            // Bonds are posted in $SWTCH, which may not be the market's
+            // collateral
+            let bond_collateral = if market.collateral_mint == arena_state.swtch_mint {
+                require!(
+                    ctx.accounts.bond_collateral.is_none(),
+                    ArenaError::InvalidCollateral
+                );
+                &mut ctx.accounts.collateral
+            } else {
+                ctx.accounts.bond_collateral.as_mut()
+                    .ok_or(ArenaError::InvalidCollateral)?
+            };
+            bond_collateral.accrued_fees = bond_collateral.accrued_fees.checked_add(bond)
+                .ok_or(ArenaError::Overflow)?;
+        } else {
+            let challenger_account = &mut ctx.accounts.challenger_account;
+            challenger_account.balance = challenger_account.balance.checked_add(bond)
+                .ok_or(ArenaError::Overflow)?;
+        }
+
+        market.outcome = Some(outcome);
+        market.fee_collected = fee_collected;
+        market.challenge_bond = 0;
+        market.status = MarketStatus::Finalized;
+
+        emit!(DisputeSettled {
+            market: market.key(),
+            challenger: market.challenger,
+            outcome,
+            upheld,
+            bond,
+            fee_collected,
         });
 
         Ok(())
     }
-}
-
-// ============================================================================
-// ACCOUNTS
-// ============================================================================
+
+    /// Finalize an unchallenged market once its dispute period has passed
+    /// and escrow its protocol fee
+    /// Can be called by anyone
+    pub fn finalize_market(ctx: Context<FinalizeMarket>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        require!(
+            market.status == MarketStatus::Resolved,
+            ArenaError::MarketNotChallengeable
+        );
+
+        let clock = Clock::get()?;
+        require!(
+            clock.unix_timestamp >= market.dispute_deadline,
+            ArenaError::DisputePeriodActive
+        );
+
+        let fee_collected = market.fee_collected;
+        escrow_fee(market, &mut ctx.accounts.collateral, fee_collected)?;
+        market.status = MarketStatus::Finalized;
+
+        emit!(MarketFinalized {
+            market: market.key(),
+            outcome: market.outcome.ok_or(ArenaError::MarketNotResolved)?,
+            fee_collected: market.fee_collected,
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a market that has not been finalized (authority or emergency resolver)
+    /// Every position can then reclaim its stake through `claim_winnings`
+    pub fn cancel_market(ctx: Context<CancelMarket>, reason: CancelReason) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let clock = Clock::get()?;
+        let cancellable = match market.status {
+            MarketStatus::Open | MarketStatus::Locked | MarketStatus::Disputed => true,
+            // Past the dispute period the outcome stands unchallenged
+            MarketStatus::Resolved => clock.unix_timestamp < market.dispute_deadline,
+            MarketStatus::Finalized | MarketStatus::Cancelled => false,
+        };
+        require!(cancellable, ArenaError::MarketNotCancellable);
+
+        // Give a pending challenger their bond back
+        if market.status == MarketStatus::Disputed {
+            let challenger_account = ctx.accounts.challenger_account.as_mut()
+                .ok_or(ArenaError::ChallengerAccountRequired)?;
+            challenger_account.balance = challenger_account.balance
+                .checked_add(market.challenge_bond)
+                .ok_or(ArenaError::Overflow)?;
+            market.challenge_bond = 0;
+        }
+
+        // Cancelled markets settle as void, so no fee is ever escrowed and
+        // claims open immediately
+        market.resolved = true;
+        market.dispute_deadline = clock.unix_timestamp;
+        market.outcome = Some(MarketOutcome::Void);
+        market.fee_collected = 0;
+        market.status = MarketStatus::Cancelled;
+        market.cancel_reason = Some(reason);
+
+        emit!(MarketCancelled {
+            market: market.key(),
+            cancelled_by: ctx.accounts.operator.key(),
+            reason,
+            total_pool: market.total_pool()?,
+        });
+
+        Ok(())
+    }
+
+    /// Claim winnings from a resolved market
+    /// Only possible once the market is finalized or cancelled
+    pub fn claim_winnings(ctx: Context<ClaimWinnings>, close_position: bool) -> Result<()> {
+        let owner = ctx.accounts.user.key();
+        ctx.accounts.referral.init_if_new(owner, ctx.bumps.referral);
+        let referrer = ctx.accounts.referral.referrer;
+        if let Some(referrer_account) = ctx.accounts.referrer_account.as_mut() {
+            referrer_account.init_if_new(
+                referrer,
+                ctx.accounts.market.collateral_mint,
+                ctx.bumps.referrer_account,
+            );
+        }
+
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.position;
+        
+        require!(!position.claimed, ArenaError::AlreadyClaimed);
+        market.check_claimable()?;
+
+        settle_position(
+            market,
+            position,
+            &mut ctx.accounts.user_account,
+            &mut ctx.accounts.collateral,
+            referrer,
+            ctx.accounts.referrer_account.as_deref_mut(),
+        )?;
+
+        market.open_positions = market.open_positions.checked_sub(1)
+            .ok_or(ArenaError::Underflow)?;
+
+        // Return the position's rent to the user
+        if close_position {
+            ctx.accounts.position.close(ctx.accounts.user.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    /// Settle a position left unclaimed past the claim expiry into its
+    /// owner's balance and close it, returning the rent to the owner
+    /// Can be called by anyone
+    pub fn sweep_position(ctx: Context<SweepPosition>) -> Result<()> {
+        let owner = ctx.accounts.owner.key();
+        ctx.accounts.referral.init_if_new(owner, ctx.bumps.referral);
+        let referrer = ctx.accounts.referral.referrer;
+        if let Some(referrer_account) = ctx.accounts.referrer_account.as_mut() {
+            referrer_account.init_if_new(
+                referrer,
+                ctx.accounts.market.collateral_mint,
+                ctx.bumps.referrer_account,
+            );
+        }
+
+        let market = &mut ctx.accounts.market;
+        let position = &mut ctx.accounts.position;
+
+        require!(!position.claimed, ArenaError::AlreadyClaimed);
+        let clock = Clock::get()?;
+        market.check_claimable()?;
+        let expiry = market.dispute_deadline
+            .checked_add(ctx.accounts.arena_state.claim_expiry_secs)
+            .ok_or(ArenaError::Overflow)?;
+        require!(clock.unix_timestamp >= expiry, ArenaError::ClaimNotExpired);
+
+        settle_position(
+            market,
+            position,
+            &mut ctx.accounts.user_account,
+            &mut ctx.accounts.collateral,
+            referrer,
+            ctx.accounts.referrer_account.as_deref_mut(),
+        )?;
+
+        market.open_positions = market.open_positions.checked_sub(1)
+            .ok_or(ArenaError::Underflow)?;
+
+        emit!(PositionSwept {
+            user: ctx.accounts.owner.key(),
+            market: market.key(),
+            swept_by: ctx.accounts.sweeper.key(),
+        });
+
+        ctx.accounts.position.close(ctx.accounts.owner.to_account_info())?;
+
+        Ok(())
+    }
+
+    /// Close a final market once every position has been settled, returning
+    /// its rent to the authority
+    /// Can be called by anyone
+    pub fn close_market(ctx: Context<CloseMarket>) -> Result<()> {
+        let market = &ctx.accounts.market;
+        require!(
+            matches!(market.status, MarketStatus::Finalized | MarketStatus::Cancelled),
+            ArenaError::MarketNotFinal
+        );
+        require!(market.open_positions == 0, ArenaError::PositionsStillOpen);
+        require!(
+            market.creation_bond == 0 && market.creator_fee == 0,
+            ArenaError::CreatorFundsUnsettled
+        );
+        require!(
+            (market.settlement.mode == SettlementMode::Spot) == ctx.accounts.samples.is_none(),
+            ArenaError::InvalidSamplesAccount
+        );
+
+        // Whatever the pro rata referral split left over goes to the protocol
+        let referral_dust = market.referral_reserve.checked_sub(market.referral_paid)
+            .ok_or(ArenaError::Underflow)?;
+        let collateral = &mut ctx.accounts.collateral;
+        collateral.accrued_fees = collateral.accrued_fees.checked_add(referral_dust)
+            .ok_or(ArenaError::Overflow)?;
+
+        emit!(MarketClosed {
+            market: market.key(),
+            market_id: market.id,
+            rent_receiver: ctx.accounts.rent_receiver.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Refund a creator's bond once their market is finalized, or slash it
+    /// into the $SWTCH fees if the market was cancelled
+    /// Can be called by anyone
+    pub fn settle_creation_bond(ctx: Context<SettleCreationBond>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        let bond = market.creation_bond;
+        require!(bond > 0, ArenaError::NoCreationBond);
+
+        let refunded = match market.status {
+            MarketStatus::Finalized => {
+                let creator_account = &mut ctx.accounts.creator_account;
+                creator_account.balance = creator_account.balance.checked_add(bond)
+                    .ok_or(ArenaError::Overflow)?;
+                true
+            }
+            MarketStatus::Cancelled => {
+                let swtch_collateral = &mut ctx.accounts.swtch_collateral;
+                swtch_collateral.accrued_fees = swtch_collateral.accrued_fees.checked_add(bond)
+                    .ok_or(ArenaError::Overflow)?;
+                false
+            }
+            _ => return err!(ArenaError::MarketNotFinal),
+        };
+        market.creation_bond = 0;
+
+        emit!(CreationBondSettled {
+            market: market.key(),
+            creator: market.creator,
+            bond,
+            refunded,
+        });
+
+        Ok(())
+    }
+
+    /// Credit a market's creator fee to the creator's balance in the
+    /// market's collateral
+    pub fn claim_creator_fee(ctx: Context<ClaimCreatorFee>) -> Result<()> {
+        let market = &mut ctx.accounts.market;
+        require!(market.status == MarketStatus::Finalized, ArenaError::MarketNotFinal);
+        let amount = market.creator_fee;
+        require!(amount > 0, ArenaError::NoCreatorFee);
+        market.creator_fee = 0;
+
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.user = ctx.accounts.creator.key();
+        user_account.mint = market.collateral_mint;
+        user_account.balance = user_account.balance.checked_add(amount)
+            .ok_or(ArenaError::Overflow)?;
+        user_account.bump = ctx.bumps.user_account;
+
+        emit!(CreatorFeeClaimed {
+            market: market.key(),
+            creator: market.creator,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Move fees escrowed in one collateral mint from its vault to the
+    /// treasury's token account for that mint
+    /// Can be called by anyone; funds only ever go to `arena_state.treasury`
+    pub fn sweep_fees(ctx: Context<SweepFees>) -> Result<()> {
+        let amount = ctx.accounts.collateral.accrued_fees;
+        require!(amount > 0, ArenaError::NoFeesToSweep);
+
+        // Update state first (checks-effects-interactions)
+        ctx.accounts.collateral.accrued_fees = 0;
+
+        let arena_state = &ctx.accounts.arena_state;
+        let seeds = &[
+            b"arena_state".as_ref(),
+            &[arena_state.bump],
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.vault.to_account_info(),
+            mint: ctx.accounts.mint.to_account_info(),
+            to: ctx.accounts.treasury.to_account_info(),
+            authority: ctx.accounts.arena_state.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.mint.decimals)?;
+
+        emit!(FeesSwept {
+            treasury: ctx.accounts.treasury.key(),
+            mint: ctx.accounts.collateral.mint,
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Add a mint to the collateral allowlist and create its vault
+    /// (admin only)
+    pub fn add_collateral(ctx: Context<AddCollateral>) -> Result<()> {
+        check_mint_extensions(&ctx.accounts.mint.to_account_info())?;
+
+        let collateral = &mut ctx.accounts.collateral;
+        collateral.mint = ctx.accounts.mint.key();
+        collateral.enabled = true;
+        collateral.total_volume = 0;
+        collateral.accrued_fees = 0;
+        collateral.bump = ctx.bumps.collateral;
+
+        emit!(CollateralAdded {
+            mint: collateral.mint,
+            vault: ctx.accounts.vault.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Allow or stop new deposits and markets in a collateral mint
+    /// (admin only); existing balances can always be withdrawn
+    pub fn set_collateral_enabled(
+        ctx: Context<SetCollateralEnabled>,
+        enabled: bool,
+    ) -> Result<()> {
+        let collateral = &mut ctx.accounts.collateral;
+        collateral.enabled = enabled;
+
+        emit!(CollateralUpdated {
+            mint: collateral.mint,
+            enabled,
+        });
+
+        Ok(())
+    }
+
+    /// Allowlist an oracle feed for new markets (admin only)
+    pub fn approve_feed(ctx: Context<ApproveFeed>, params: ApprovedFeedParams) -> Result<()> {
+        params.validate()?;
+
+        let approved_feed = &mut ctx.accounts.approved_feed;
+        approved_feed.feed = ctx.accounts.oracle_feed.key();
+        approved_feed.bump = ctx.bumps.approved_feed;
+        approved_feed.apply(params);
+
+        emit!(FeedApproved {
+            feed: approved_feed.feed,
+            oracle_kind: approved_feed.oracle_kind,
+            oracle_queue: approved_feed.oracle_queue,
+            min_oracle_results: approved_feed.min_oracle_results,
+            require_locked: approved_feed.require_locked,
+        });
+
+        Ok(())
+    }
+
+    /// Change the requirements of an approved feed (admin only); markets
+    /// already listed on it are unaffected
+    pub fn update_feed(ctx: Context<UpdateFeed>, params: ApprovedFeedParams) -> Result<()> {
+        params.validate()?;
+
+        let approved_feed = &mut ctx.accounts.approved_feed;
+        approved_feed.apply(params);
+
+        emit!(FeedUpdated {
+            feed: approved_feed.feed,
+            oracle_kind: approved_feed.oracle_kind,
+            oracle_queue: approved_feed.oracle_queue,
+            min_oracle_results: approved_feed.min_oracle_results,
+            require_locked: approved_feed.require_locked,
+        });
+
+        Ok(())
+    }
+
+    /// Take a feed off the allowlist (admin only); markets already listed
+    /// on it still resolve against it
+    pub fn remove_feed(ctx: Context<RemoveFeed>) -> Result<()> {
+        emit!(FeedRemoved { feed: ctx.accounts.approved_feed.feed });
+
+        Ok(())
+    }
+
+    /// Pause one or more scopes (`PAUSE_*` bits) (admin or pauser)
+    pub fn pause(ctx: Context<SetPause>, scopes: u8) -> Result<()> {
+        require!(
+            scopes != 0 && scopes & !PAUSE_ALL == 0,
+            ArenaError::InvalidPauseScope
+        );
+
+        let arena_state = &mut ctx.accounts.arena_state;
+        arena_state.paused |= scopes;
+
+        emit!(ArenaPaused {
+            scopes,
+            paused: arena_state.paused,
+        });
+
+        Ok(())
+    }
+
+    /// Resume one or more paused scopes (`PAUSE_*` bits) (admin or pauser)
+    pub fn unpause(ctx: Context<SetPause>, scopes: u8) -> Result<()> {
+        require!(
+            scopes != 0 && scopes & !PAUSE_ALL == 0,
+            ArenaError::InvalidPauseScope
+        );
+
+        let arena_state = &mut ctx.accounts.arena_state;
+        arena_state.paused &= !scopes;
+
+        emit!(ArenaUnpaused {
+            scopes,
+            paused: arena_state.paused,
+        });
+
+        Ok(())
+    }
+
+    /// Queue an admin parameter change behind the timelock
+    /// (admin, or fee admin for fee changes)
+    pub fn queue_change(ctx: Context<QueueChange>, change: AdminChange) -> Result<()> {
+        change.validate()?;
+
+        let clock = Clock::get()?;
+        let arena_state = &mut ctx.accounts.arena_state;
+        let executable_at = clock.unix_timestamp
+            .checked_add(arena_state.timelock_delay_secs)
+            .ok_or(ArenaError::Overflow)?;
+
+        let change_id = arena_state.total_changes;
+        arena_state.total_changes = arena_state.total_changes.checked_add(1)
+            .ok_or(ArenaError::Overflow)?;
+
+        let pending_change = &mut ctx.accounts.pending_change;
+        pending_change.id = change_id;
+        pending_change.proposer = ctx.accounts.proposer.key();
+        pending_change.payer = ctx.accounts.payer.key();
+        pending_change.change = change;
+        pending_change.executable_at = executable_at;
+        pending_change.bump = ctx.bumps.pending_change;
+
+        emit!(AdminChangeQueued {
+            change_id,
+            proposer: pending_change.proposer,
+            change,
+            executable_at,
+        });
+
+        Ok(())
+    }
+
+    /// Apply a queued change once its delay has passed
+    /// Can be called by anyone; the rent goes back to whoever paid for it
+    pub fn execute_change(ctx: Context<ExecuteChange>) -> Result<()> {
+        let clock = Clock::get()?;
+        let pending_change = &ctx.accounts.pending_change;
+        require!(
+            clock.unix_timestamp >= pending_change.executable_at,
+            ArenaError::TimelockNotElapsed
+        );
+
+        let change = pending_change.change;
+        let arena_state = &mut ctx.accounts.arena_state;
+        match change {
+            AdminChange::ProtocolFee { fee_bps } => {
+                let old_fee = arena_state.protocol_fee_bps;
+                arena_state.protocol_fee_bps = fee_bps;
+                emit!(FeeUpdated {
+                    old_fee_bps: old_fee,
+                    new_fee_bps: fee_bps,
+                });
+            }
+            AdminChange::ExitFee { fee_bps } => {
+                let old_exit_fee = arena_state.exit_fee_bps;
+                arena_state.exit_fee_bps = fee_bps;
+                emit!(ExitFeeUpdated {
+                    old_exit_fee_bps: old_exit_fee,
+                    new_exit_fee_bps: fee_bps,
+                });
+            }
+            AdminChange::Treasury { treasury } => {
+                // The new treasury still has to sign `accept_treasury`
+                arena_state.pending_treasury = treasury;
+                emit!(TreasuryTransferProposed {
+                    treasury: arena_state.treasury,
+                    pending_treasury: treasury,
+                });
+            }
+            AdminChange::BetLimits { bet_limits, max_open_exposure } => {
+                arena_state.bet_limits = bet_limits;
+                arena_state.max_open_exposure = max_open_exposure;
+                emit!(BetLimitsUpdated { bet_limits, max_open_exposure });
+            }
+            AdminChange::OraclePolicy { oracle_policy } => {
+                arena_state.oracle_policy = oracle_policy;
+                emit!(OraclePolicyUpdated { oracle_policy });
+            }
+            AdminChange::TimelockDelay { delay_secs } => {
+                let old_delay = arena_state.timelock_delay_secs;
+                arena_state.timelock_delay_secs = delay_secs;
+                emit!(TimelockDelayUpdated {
+                    old_delay_secs: old_delay,
+                    new_delay_secs: delay_secs,
+                });
+            }
+            AdminChange::ReferralFee { fee_bps } => {
+                let old_fee = arena_state.referral_fee_bps;
+                arena_state.referral_fee_bps = fee_bps;
+                emit!(ReferralFeeUpdated {
+                    old_fee_bps: old_fee,
+                    new_fee_bps: fee_bps,
+                });
+            }
+            AdminChange::DisputeConfig { dispute_period_secs, dispute_bond } => {
+                arena_state.dispute_period_secs = dispute_period_secs;
+                arena_state.dispute_bond = dispute_bond;
+                emit!(DisputeConfigUpdated {
+                    dispute_period_secs,
+                    dispute_bond,
+                });
+            }
+            AdminChange::ClaimExpiry { claim_expiry_secs } => {
+                arena_state.claim_expiry_secs = claim_expiry_secs;
+                emit!(ClaimExpiryUpdated { claim_expiry_secs });
+            }
+            AdminChange::CreationConfig { open_creation, creation_bond, creator_fee_bps } => {
+                arena_state.open_creation = open_creation;
+                arena_state.creation_bond = creation_bond;
+                arena_state.creator_fee_bps = creator_fee_bps;
+                emit!(CreationConfigUpdated {
+                    open_creation,
+                    creation_bond,
+                    creator_fee_bps,
+                });
+            }
+        }
+
+        emit!(AdminChangeExecuted {
+            change_id: pending_change.id,
+            change,
+        });
+
+        Ok(())
+    }
+
+    /// Drop a queued change before it is executed (admin only)
+    pub fn cancel_change(ctx: Context<CancelChange>) -> Result<()> {
+        let pending_change = &ctx.accounts.pending_change;
+
+        emit!(AdminChangeCancelled {
+            change_id: pending_change.id,
+            change: pending_change.change,
+        });
+
+        Ok(())
+    }
+
+    /// Grant one or more roles (`ROLE_*` bits) to a key (admin only)
+    pub fn grant_role(ctx: Context<ManageRoles>, member: Pubkey, roles: u8) -> Result<()> {
+        require!(roles != 0 && roles & !ROLE_ALL == 0, ArenaError::InvalidRole);
+
+        let member_roles = ctx.accounts.role_registry.grant(member, roles)?;
+
+        emit!(RoleGranted {
+            member,
+            roles,
+            member_roles,
+        });
+
+        Ok(())
+    }
+
+    /// Revoke one or more roles (`ROLE_*` bits) from a key (admin only)
+    pub fn revoke_role(ctx: Context<ManageRoles>, member: Pubkey, roles: u8) -> Result<()> {
+        require!(roles != 0 && roles & !ROLE_ALL == 0, ArenaError::InvalidRole);
+
+        let member_roles = ctx.accounts.role_registry.revoke(member, roles)?;
+
+        emit!(RoleRevoked {
+            member,
+            roles,
+            member_roles,
+        });
+
+        Ok(())
+    }
+
+    /// Propose a new authority, which must accept before it takes over
+    /// (admin only)
+    pub fn propose_authority(ctx: Context<TransferAuthority>, new_authority: Pubkey) -> Result<()> {
+        require!(new_authority != Pubkey::default(), ArenaError::InvalidAuthority);
+
+        let arena_state = &mut ctx.accounts.arena_state;
+        arena_state.pending_authority = new_authority;
+
+        emit!(AuthorityTransferProposed {
+            authority: arena_state.authority,
+            pending_authority: new_authority,
+        });
+
+        Ok(())
+    }
+
+    /// Accept a proposed authority transfer (signed by the new authority)
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let arena_state = &mut ctx.accounts.arena_state;
+        let old_authority = arena_state.authority;
+        arena_state.authority = arena_state.pending_authority;
+        arena_state.pending_authority = Pubkey::default();
+
+        emit!(AuthorityTransferred {
+            old_authority,
+            new_authority: arena_state.authority,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw a pending authority transfer (admin only)
+    pub fn cancel_authority_transfer(ctx: Context<TransferAuthority>) -> Result<()> {
+        let arena_state = &mut ctx.accounts.arena_state;
+        require!(
+            arena_state.pending_authority != Pubkey::default(),
+            ArenaError::NoPendingTransfer
+        );
+
+        let cancelled = arena_state.pending_authority;
+        arena_state.pending_authority = Pubkey::default();
+
+        emit!(AuthorityTransferCancelled { cancelled });
+
+        Ok(())
+    }
+
+    /// Accept a proposed treasury change (signed by the new treasury)
+    pub fn accept_treasury(ctx: Context<AcceptTreasury>) -> Result<()> {
+        let arena_state = &mut ctx.accounts.arena_state;
+        let old_treasury = arena_state.treasury;
+        arena_state.treasury = arena_state.pending_treasury;
+        arena_state.pending_treasury = Pubkey::default();
+
+        emit!(TreasuryTransferred {
+            old_treasury,
+            new_treasury: arena_state.treasury,
+        });
+
+        Ok(())
+    }
+
+    /// Withdraw a pending treasury change (admin only)
+    pub fn cancel_treasury_transfer(ctx: Context<TransferAuthority>) -> Result<()> {
+        let arena_state = &mut ctx.accounts.arena_state;
+        require!(
+            arena_state.pending_treasury != Pubkey::default(),
+            ArenaError::NoPendingTransfer
+        );
+
+        let cancelled = arena_state.pending_treasury;
+        arena_state.pending_treasury = Pubkey::default();
+
+        emit!(TreasuryTransferCancelled { cancelled });
+
+        Ok(())
+    }
+
+    /// Create the built-in M-of-N admin council (admin only)
+    /// Hand it control with `propose_authority` and a council action that
+    /// calls `accept_authority`
+    pub fn create_council(
+        ctx: Context<CreateCouncil>,
+        members: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        AdminCouncil::validate(&members, threshold)?;
+
+        let council = &mut ctx.accounts.council;
+        council.members = members.clone();
+        council.threshold = threshold;
+        council.total_actions = 0;
+        council.bump = ctx.bumps.council;
+
+        emit!(CouncilUpdated { members, threshold });
+
+        Ok(())
+    }
+
+    /// Replace the council's members and threshold
+    /// Only the council itself can call this, through an approved action
+    pub fn update_council(
+        ctx: Context<UpdateCouncil>,
+        members: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        AdminCouncil::validate(&members, threshold)?;
+
+        let council = &mut ctx.accounts.council;
+        council.members = members.clone();
+        council.threshold = threshold;
+
+        emit!(CouncilUpdated { members, threshold });
+
+        Ok(())
+    }
+
+    /// Propose an arena instruction for the council to sign (council member)
+    /// The proposer's approval is counted automatically
+    pub fn propose_council_action(
+        ctx: Context<ProposeCouncilAction>,
+        accounts: Vec<CouncilAccountMeta>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        require!(
+            accounts.len() <= MAX_COUNCIL_ACTION_ACCOUNTS
+                && data.len() <= MAX_COUNCIL_ACTION_DATA_LEN,
+            ArenaError::InvalidCouncilAction
+        );
+
+        let council = &mut ctx.accounts.council;
+        let action_id = council.total_actions;
+        council.total_actions = council.total_actions.checked_add(1)
+            .ok_or(ArenaError::Overflow)?;
+
+        let action = &mut ctx.accounts.action;
+        action.id = action_id;
+        action.proposer = ctx.accounts.proposer.key();
+        action.accounts = accounts;
+        action.data = data;
+        action.approvals = vec![action.proposer];
+        action.bump = ctx.bumps.action;
+
+        emit!(CouncilActionProposed {
+            action_id,
+            proposer: action.proposer,
+        });
+
+        Ok(())
+    }
+
+    /// Approve a proposed council action (council member)
+    pub fn approve_council_action(ctx: Context<ApproveCouncilAction>) -> Result<()> {
+        let member = ctx.accounts.member.key();
+        let council = &ctx.accounts.council;
+        let action = &mut ctx.accounts.action;
+        require!(!action.approvals.contains(&member), ArenaError::AlreadyApproved);
+
+        // Forget approvals from removed members so the list stays in bounds
+        action.approvals.retain(|approver| council.members.contains(approver));
+        action.approvals.push(member);
+
+        emit!(CouncilActionApproved {
+            action_id: action.id,
+            member,
+            approvals: council.approvals_for(action),
+        });
+
+        Ok(())
+    }
+
+    /// Sign and run an approved council action as the council PDA
+    /// Can be called by anyone once the threshold is met; the action's
+    /// accounts are passed as remaining accounts
+    pub fn execute_council_action<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteCouncilAction<'info>>,
+    ) -> Result<()> {
+        let council = &ctx.accounts.council;
+        let action = &ctx.accounts.action;
+        require!(
+            council.approvals_for(action) >= council.threshold,
+            ArenaError::CouncilThresholdNotMet
+        );
+
+        let instruction = Instruction {
+            program_id: crate::ID,
+            accounts: action.accounts.iter()
+                .map(|meta| anchor_lang::solana_program::instruction::AccountMeta {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: action.data.clone(),
+        };
+
+        let mut account_infos = ctx.remaining_accounts.to_vec();
+        account_infos.push(council.to_account_info());
+
+        let seeds = &[b"council".as_ref(), &[council.bump]];
+        invoke_signed(&instruction, &account_infos, &[&seeds[..]])?;
+
+        emit!(CouncilActionExecuted { action_id: action.id });
+
+        Ok(())
+    }
+
+    /// Withdraw a council action before it is executed (its proposer only)
+    pub fn cancel_council_action(ctx: Context<CancelCouncilAction>) -> Result<()> {
+        emit!(CouncilActionCancelled { action_id: ctx.accounts.action.id });
+
+        Ok(())
+    }
+}
+
+/// Latest result of a Switchboard feed, normalised across feed types
+pub struct OracleReading {
+    pub value: i128,
+    pub std_deviation: i128,
+    pub num_results: u32,
+    pub timestamp: i64,
+    /// Round history of a V2 aggregator (default for pull feeds)
+    pub history_buffer: Pubkey,
+    /// Oracle queue serving the feed
+    pub queue: Pubkey,
+    /// The feed's configuration can no longer be changed by its authority
+    pub locked: bool,
+}
+
+impl OracleReading {
+    /// Deserializes `oracle_feed` as `kind`, checking the owning program
+    pub fn load(oracle_feed: &AccountInfo, kind: OracleKind) -> Result<Self> {
+        match kind {
+            OracleKind::AggregatorV2 => {
+                require_keys_eq!(
+                    *oracle_feed.owner,
+                    SWITCHBOARD_PROGRAM_ID,
+                    ArenaError::InvalidOracle
+                );
+                let data = oracle_feed.try_borrow_data()?;
+                let feed_data = AggregatorAccountData::new_from_bytes(&data)?;
+                let round = feed_data.latest_confirmed_round;
+
+                Ok(Self {
+                    value: feed_data.get_result()?.try_into()?,
+                    std_deviation: round.std_deviation.try_into()?,
+                    num_results: round.num_success,
+                    timestamp: round.round_open_timestamp,
+                    history_buffer: feed_data.history_buffer,
+                    queue: feed_data.queue_pubkey,
+                    locked: feed_data.is_locked,
+                })
+            }
+            OracleKind::PullFeed => {
+                require_keys_eq!(
+                    *oracle_feed.owner,
+                    SWITCHBOARD_ON_DEMAND_PROGRAM_ID,
+                    ArenaError::InvalidOracle
+                );
+                let feed_data = PullFeedAccountData::parse(oracle_feed.data.borrow())
+                    .map_err(|_| error!(ArenaError::InvalidOracle))?;
+                // A zero slot means the feed has never produced a result
+                require!(feed_data.result.slot > 0, ArenaError::StaleOracle);
+
+                // Read in whole units, the same scale as V2 results, so
+                // strikes and bounds mean the same for either feed kind
+                Ok(Self {
+                    value: from_pull_feed(feed_data.result.value),
+                    std_deviation: from_pull_feed(feed_data.result.std_dev),
+                    num_results: feed_data.result.num_samples as u32,
+                    timestamp: feed_data.last_update_timestamp,
+                    history_buffer: Pubkey::default(),
+                    queue: feed_data.queue,
+                    // Pull feeds have no lock flag; dropping the authority
+                    // freezes their job definition instead
+                    locked: feed_data.authority == Pubkey::default(),
+                })
+            }
+        }
+    }
+
+    /// Finds the first round of a V2 aggregator at or after `target` in its
+    /// history buffer, or `None` if there is no usable one
+    ///
+    /// History rows carry no responder count or spread, so the rest of
+    /// `policy` can only be checked when that round is still the
+    /// aggregator's latest confirmed round.
+    pub fn load_as_of(
+        oracle_feed: &AccountInfo,
+        history_buffer: Option<&AccountInfo>,
+        policy: &OraclePolicy,
+        target: i64,
+    ) -> Result<Option<OracleSample>> {
+        let reading = Self::load(oracle_feed, OracleKind::AggregatorV2)?;
+
+        let history_buffer = history_buffer.ok_or(ArenaError::InvalidHistoryBuffer)?;
+        require_keys_eq!(
+            history_buffer.key(),
+            reading.history_buffer,
+            ArenaError::InvalidHistoryBuffer
+        );
+        let history = AggregatorHistoryBuffer::new(history_buffer)?;
+
+        let Some(row) = first_round_as_of(&history.rows, target, policy.max_staleness_secs) else {
+            return Ok(None);
+        };
+        if row.timestamp == reading.timestamp && reading.check_quality(policy).is_err() {
+            return Ok(None);
+        }
+
+        Ok(Some(OracleSample {
+            timestamp: row.timestamp,
+            value: row.value.try_into()?,
+        }))
+    }
+
+    /// Returns the value if the reading satisfies `policy`
+    pub fn checked_value(&self, policy: &OraclePolicy, now: i64) -> Result<i128> {
+        require!(
+            now.saturating_sub(self.timestamp) <= policy.max_staleness_secs,
+            ArenaError::StaleOracle
+        );
+        self.check_quality(policy)?;
+
+        Ok(self.value)
+    }
+
+    /// Checks the responder count and spread against `policy`
+    pub fn check_quality(&self, policy: &OraclePolicy) -> Result<()> {
+        require!(
+            self.num_results >= policy.min_oracle_results,
+            ArenaError::InsufficientOracleResults
+        );
+        if policy.max_std_deviation > 0 {
+            require!(
+                self.std_deviation <= policy.max_std_deviation,
+                ArenaError::OracleDeviationTooHigh
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads an 18-decimal pull feed value in whole units, truncating toward
+/// zero like V2 aggregator results
+fn from_pull_feed(value: i128) -> i128 {
+    value / PULL_FEED_SCALE
+}
+
+/// First V2 history row at or after `target`, if it landed within
+/// `max_staleness` of it
+///
+/// Returns `None` if no row predates `target`: the ring buffer has wrapped
+/// past it, so an earlier round may have been overwritten.
+fn first_round_as_of(
+    rows: &[AggregatorHistoryRow],
+    target: i64,
+    max_staleness: i64,
+) -> Option<AggregatorHistoryRow> {
+    if !rows.iter().any(|row| row.timestamp < target) {
+        return None;
+    }
+
+    rows.iter()
+        .filter(|row| row.timestamp >= target)
+        .min_by_key(|row| row.timestamp)
+        .filter(|row| row.timestamp <= target.saturating_add(max_staleness))
+        .copied()
+}
+
+/// Fee charged on a market's total pool
+fn protocol_fee(total_pool: u64, fee_bps: u16) -> Result<u64> {
+    let fee = (total_pool as u128)
+        .checked_mul(fee_bps as u128)
+        .ok_or(ArenaError::Overflow)?
+        .checked_div(10_000)
+        .ok_or(ArenaError::DivisionByZero)?;
+
+    Ok(fee as u64)
+}
+
+/// Escrows a final market's protocol fee for the treasury, keeping the
+/// creator's share on the market until `claim_creator_fee` and the referral
+/// reserve until winners settle
+fn escrow_fee(market: &mut Market, collateral: &mut CollateralConfig, fee: u64) -> Result<()> {
+    let creator_fee = protocol_fee(fee, market.creator_fee_bps)?;
+    let protocol_share = fee.checked_sub(creator_fee).ok_or(ArenaError::Underflow)?;
+    let referral_reserve = protocol_fee(protocol_share, market.referral_fee_bps)?;
+    market.creator_fee = creator_fee;
+    market.referral_reserve = referral_reserve;
+    collateral.accrued_fees = collateral.accrued_fees
+        .checked_add(protocol_share.checked_sub(referral_reserve).ok_or(ArenaError::Underflow)?)
+        .ok_or(ArenaError::Overflow)?;
+
+    Ok(())
+}
+
+/// Rejects Token-2022 mints with extensions the vaults cannot safely hold
+/// (transfer hooks, permanent delegates, frozen-by-default accounts, ...)
+fn check_mint_extensions(mint: &AccountInfo) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let data = mint.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let supported = mint_state.get_extension_types()?.iter().all(|extension| {
+        matches!(
+            extension,
+            ExtensionType::TransferFeeConfig
+                | ExtensionType::MintCloseAuthority
+                | ExtensionType::InterestBearingConfig
+                | ExtensionType::MetadataPointer
+                | ExtensionType::TokenMetadata
+        )
+    });
+    require!(supported, ArenaError::UnsupportedMintExtension);
+
+    Ok(())
+}
+
+/// Moves a wager out of the user's free balance
+fn debit_wager(user_account: &mut UserAccount, amount: u64) -> Result<()> {
+    // Check user has enough balance
+    require!(
+        user_account.balance >= amount,
+        ArenaError::InsufficientBalance
+    );
+
+    // Deduct from user balance
+    user_account.balance = user_account.balance.checked_sub(amount)
+        .ok_or(ArenaError::Underflow)?;
+    user_account.total_wagered = user_account.total_wagered.checked_add(amount)
+        .ok_or(ArenaError::Overflow)?;
+
+    Ok(())
+}
+
+/// Counts a new stake towards the user's open exposure in its mint
+///
+/// Once every counted market has reached its resolution time the stakes are
+/// no longer open, so the count starts over without waiting for claims.
+fn add_exposure(
+    user_account: &mut UserAccount,
+    amount: u64,
+    resolution_time: i64,
+    now: i64,
+    max_open_exposure: u64,
+) -> Result<()> {
+    if now >= user_account.exposure_expires_at {
+        user_account.open_exposure = 0;
+        user_account.exposure_released_at = user_account.exposure_expires_at;
+    }
+    user_account.open_exposure = user_account.open_exposure.checked_add(amount)
+        .ok_or(ArenaError::Overflow)?;
+    user_account.exposure_expires_at = user_account.exposure_expires_at.max(resolution_time);
+    require!(
+        max_open_exposure == 0 || user_account.open_exposure <= max_open_exposure,
+        ArenaError::ExposureLimitExceeded
+    );
+
+    Ok(())
+}
+
+/// Takes a stake back out of the user's open exposure, unless it was
+/// already released when the count started over
+fn release_exposure(user_account: &mut UserAccount, resolution_time: i64, amount: u64) -> Result<()> {
+    if resolution_time > user_account.exposure_released_at {
+        user_account.open_exposure = user_account.open_exposure.checked_sub(amount)
+            .ok_or(ArenaError::Underflow)?;
+    }
+
+    Ok(())
+}
+
+/// Pays out a position on a resolved market into its owner's balance and
+/// marks it claimed
+///
+/// A winner's share of the referral reserve goes to their referrer, or back
+/// to the protocol fees if they were not referred.
+fn settle_position(
+    market: &mut Market,
+    position: &mut Position,
+    user_account: &mut UserAccount,
+    collateral: &mut CollateralConfig,
+    referrer: Pubkey,
+    referrer_account: Option<&mut UserAccount>,
+) -> Result<()> {
+    // Fully reduced positions have nothing to pay and no result to record
+    if position.amount == 0 {
+        position.claimed = true;
+        return Ok(());
+    }
+
+    let outcome = market.outcome.ok_or(ArenaError::MarketNotResolved)?;
+
+    // The stake is no longer at risk, whatever the result
+    release_exposure(user_account, market.resolution_time, position.amount)?;
+
+    if outcome == MarketOutcome::Void {
+        // Return the stake without charging a fee or touching stats
+        user_account.balance = user_account.balance.checked_add(position.amount)
+            .ok_or(ArenaError::Overflow)?;
+
+        emit!(BetRefunded {
+            user: position.user,
+            market: position.market,
+            amount: position.amount,
+        });
+
+        position.claimed = true;
+        return Ok(());
+    }
+
+    // Check if user won
+    let user_won = position.is_winner(outcome);
+
+    if user_won {
+        // Winners split whatever is left after the escrowed fee
+        let pool_after_fee = market.total_pool()?
+            .checked_sub(market.fee_collected)
+            .ok_or(ArenaError::Underflow)?;
+
+        // Calculate user's share
+        let user_payout = market.payout_for(position, pool_after_fee)?;
+
+        // Credit user account
+        user_account.balance = user_account.balance.checked_add(user_payout)
+            .ok_or(ArenaError::Overflow)?;
+        user_account.total_won = user_account.total_won.checked_add(user_payout)
+            .ok_or(ArenaError::Overflow)?;
+        user_account.wins = user_account.wins.checked_add(1)
+            .ok_or(ArenaError::Overflow)?;
+        user_account.current_streak = user_account.current_streak.checked_add(1)
+            .ok_or(ArenaError::Overflow)?;
+        if user_account.current_streak > user_account.best_streak {
+            user_account.best_streak = user_account.current_streak;
+        }
+
+        emit!(WinningsClaimed {
+            user: position.user,
+            market: position.market,
+            payout: user_payout,
+        });
+
+        let referral = market.payout_for(position, market.referral_reserve)?;
+        market.referral_paid = market.referral_paid.checked_add(referral)
+            .ok_or(ArenaError::Overflow)?;
+        if referral > 0 {
+            if referrer == Pubkey::default() {
+                collateral.accrued_fees = collateral.accrued_fees.checked_add(referral)
+                    .ok_or(ArenaError::Overflow)?;
+            } else {
+                let referrer_account = referrer_account
+                    .ok_or(ArenaError::ReferrerAccountRequired)?;
+                referrer_account.balance = referrer_account.balance.checked_add(referral)
+                    .ok_or(ArenaError::Overflow)?;
+                referrer_account.referral_earnings = referrer_account.referral_earnings
+                    .checked_add(referral)
+                    .ok_or(ArenaError::Overflow)?;
+
+                emit!(ReferralRewarded {
+                    referrer: referrer_account.user,
+                    user: position.user,
+                    market: position.market,
+                    mint: referrer_account.mint,
+                    amount: referral,
+                });
+            }
+        }
+    } else {
+        // User lost - update stats
+        user_account.losses = user_account.losses.checked_add(1)
+            .ok_or(ArenaError::Overflow)?;
+        user_account.current_streak = 0;
+
+        emit!(BetLost {
+            user: position.user,
+            market: position.market,
+            amount_lost: position.amount,
+        });
+    }
+
+    position.claimed = true;
+
+    Ok(())
+}
+
+/// Pays `stake` its share of `side_share`, the part of the pool owed to a
+/// side holding `side_pool` in total
+fn pro_rata(stake: u64, side_share: u128, side_pool: u64) -> Result<u128> {
+    if stake == 0 {
+        return Ok(0);
+    }
+    let payout = (stake as u128)
+        .checked_mul(side_share)
+        .ok_or(ArenaError::Overflow)?
+        .checked_div(side_pool as u128)
+        .ok_or(ArenaError::DivisionByZero)?;
+    Ok(payout)
+}
+
+// ============================================================================
+// ACCOUNTS
+// ============================================================================
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ArenaState::INIT_SPACE,
+        seeds = [b"arena_state"],
+        bump,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + RoleRegistry::INIT_SPACE,
+        seeds = [b"roles"],
+        bump,
+    )]
+    pub role_registry: Account<'info, RoleRegistry>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + CollateralConfig::INIT_SPACE,
+        seeds = [b"collateral", swtch_mint.key().as_ref()],
+        bump,
+    )]
+    pub swtch_collateral: Account<'info, CollateralConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = swtch_mint,
+        token::authority = arena_state,
+        token::token_program = token_program,
+        seeds = [b"vault", swtch_mint.key().as_ref()],
+        bump,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub swtch_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Treasury wallet that owns the fee token account for each
+    /// collateral mint
+    pub treasury: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_DEPOSITS) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        seeds = [b"collateral", mint.key().as_ref()],
+        bump = collateral.bump,
+        constraint = collateral.enabled @ ArenaError::CollateralNotAllowed,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", mint.key().as_ref()],
+        bump,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserAccount::INIT_SPACE,
+        seeds = [b"user_account", user.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == mint.key(),
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + Referral::INIT_SPACE,
+        seeds = [b"referral", user.key().as_ref()],
+        bump,
+    )]
+    pub referral: Account<'info, Referral>,
+
+    /// Referrer's own `Referral`; only read on the user's first deposit
+    #[account(
+        mut,
+        seeds = [b"referral", referrer_referral.user.as_ref()],
+        bump = referrer_referral.bump,
+        constraint = referrer_referral.user != user.key() @ ArenaError::InvalidReferrer,
+    )]
+    pub referrer_referral: Option<Account<'info, Referral>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_WITHDRAWALS) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", mint.key().as_ref()],
+        bump,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref(), mint.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.user == user.key(),
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == mint.key(),
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(params: CreateMarketParams)]
+pub struct CreateMarket<'info> {
+    #[account(
+        mut,
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_MARKET_CREATION) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        seeds = [b"roles"],
+        bump = role_registry.bump,
+        constraint = arena_state.open_creation
+            || arena_state.authority == creator.key()
+            || role_registry.has_role(&creator.key(), ROLE_MARKET_CREATOR) @ ArenaError::Unauthorized,
+    )]
+    pub role_registry: Account<'info, RoleRegistry>,
+
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + Market::INIT_SPACE,
+        seeds = [b"market", arena_state.total_markets.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    /// Sample buffer, required for markets settled over a window
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + MarketSamples::INIT_SPACE,
+        seeds = [b"samples", market.key().as_ref()],
+        bump,
+    )]
+    pub samples: Option<Account<'info, MarketSamples>>,
+
+    #[account(
+        seeds = [b"collateral", collateral.mint.as_ref()],
+        bump = collateral.bump,
+        constraint = collateral.enabled @ ArenaError::CollateralNotAllowed,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    /// CHECK: Switchboard aggregator or pull feed, owner and layout are
+    /// checked by `OracleReading::load`
+    #[account(
+        constraint = oracle_feed.key() == params.oracle_feed @ ArenaError::InvalidOracle,
+    )]
+    pub oracle_feed: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = approved_feed.feed == params.oracle_feed @ ArenaError::FeedNotApproved,
+    )]
+    pub approved_feed: Account<'info, ApprovedFeed>,
+
+    /// Creator's $SWTCH balance; required when a creation bond is due
+    #[account(
+        mut,
+        seeds = [b"user_account", creator.key().as_ref(), arena_state.swtch_mint.as_ref()],
+        bump = creator_account.bump,
+    )]
+    pub creator_account: Option<Account<'info, UserAccount>>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceBet<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_BETTING) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", market.collateral_mint.as_ref()],
+        bump = collateral.bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref(), market.collateral_mint.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.user == user.key(),
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + Position::INIT_SPACE,
+        seeds = [b"position", market.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub position: Account<'info, Position>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReducePosition<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_BETTING) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", market.collateral_mint.as_ref()],
+        bump = collateral.bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref(), market.collateral_mint.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.user == user.key(),
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"position", market.key().as_ref(), user.key().as_ref()],
+        bump = position.bump,
+        constraint = position.user == user.key() @ ArenaError::Unauthorized,
+    )]
+    pub position: Account<'info, Position>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LockMarket<'info> {
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+}
+
+#[derive(Accounts)]
+pub struct ResolveMarket<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_RESOLUTION) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    /// CHECK: Switchboard aggregator or pull feed, owner and layout are
+    /// checked by `OracleReading::load`
+    #[account(
+        constraint = oracle_feed.key() == market.oracle_feed @ ArenaError::InvalidOracle,
+    )]
+    pub oracle_feed: UncheckedAccount<'info>,
+
+    /// CHECK: History buffer of a V2 aggregator, checked against the
+    /// aggregator by `OracleReading::load_as_of`
+    pub history_buffer: Option<UncheckedAccount<'info>>,
+
+    /// Required for markets settled over a window
+    #[account(
+        seeds = [b"samples", market.key().as_ref()],
+        bump = samples.bump,
+    )]
+    pub samples: Option<Account<'info, MarketSamples>>,
+
+    pub resolver: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RecordSample<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_RESOLUTION) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    /// Created with the market by `create_market`
+    #[account(
+        mut,
+        seeds = [b"samples", market.key().as_ref()],
+        bump = samples.bump,
+    )]
+    pub samples: Account<'info, MarketSamples>,
+
+    /// CHECK: Switchboard aggregator or pull feed, owner and layout are
+    /// checked by `OracleReading::load`
+    #[account(
+        constraint = oracle_feed.key() == market.oracle_feed @ ArenaError::InvalidOracle,
+    )]
+    pub oracle_feed: UncheckedAccount<'info>,
+
+    pub cranker: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ChallengeResolution<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_RESOLUTION) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", challenger.key().as_ref(), arena_state.swtch_mint.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.user == challenger.key(),
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    pub challenger: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SettleDispute<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_RESOLUTION) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        seeds = [b"roles"],
+        bump = role_registry.bump,
+        constraint = arena_state.authority == resolver.key()
+            || role_registry.has_role(&resolver.key(), ROLE_EMERGENCY_RESOLVER) @ ArenaError::Unauthorized,
+    )]
+    pub role_registry: Account<'info, RoleRegistry>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", market.collateral_mint.as_ref()],
+        bump = collateral.bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    /// $SWTCH collateral the bond is slashed into, required when the market
+    /// uses another collateral mint
+    #[account(
+        mut,
+        seeds = [b"collateral", arena_state.swtch_mint.as_ref()],
+        bump = bond_collateral.bump,
+    )]
+    pub bond_collateral: Option<Account<'info, CollateralConfig>>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", market.challenger.as_ref(), arena_state.swtch_mint.as_ref()],
+        bump = challenger_account.bump,
+    )]
+    pub challenger_account: Account<'info, UserAccount>,
+
+    pub resolver: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeMarket<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_RESOLUTION) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", market.collateral_mint.as_ref()],
+        bump = collateral.bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CancelMarket<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        seeds = [b"roles"],
+        bump = role_registry.bump,
+        constraint = arena_state.authority == operator.key()
+            || role_registry.has_role(&operator.key(), ROLE_EMERGENCY_RESOLVER) @ ArenaError::Unauthorized,
+    )]
+    pub role_registry: Account<'info, RoleRegistry>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    /// Required when the market is disputed, to refund the bond
+    #[account(
+        mut,
+        seeds = [b"user_account", market.challenger.as_ref(), arena_state.swtch_mint.as_ref()],
+        bump = challenger_account.bump,
+    )]
+    pub challenger_account: Option<Account<'info, UserAccount>>,
+
+    pub operator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimWinnings<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_CLAIMS) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref(), market.collateral_mint.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.user == user.key(),
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", market.collateral_mint.as_ref()],
+        bump = collateral.bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + Referral::INIT_SPACE,
+        seeds = [b"referral", user.key().as_ref()],
+        bump,
+    )]
+    pub referral: Account<'info, Referral>,
+
+    /// Balance of the user's referrer in the market's mint, created if
+    /// needed; required when a referred user won
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + UserAccount::INIT_SPACE,
+        seeds = [b"user_account", referral.referrer.as_ref(), market.collateral_mint.as_ref()],
+        bump,
+        constraint = referral.referrer != Pubkey::default() @ ArenaError::InvalidReferrer,
+    )]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"position", market.key().as_ref(), user.key().as_ref()],
+        bump = position.bump,
+        constraint = position.user == user.key() @ ArenaError::Unauthorized,
+        constraint = position.market == market.key() @ ArenaError::InvalidPosition,
+    )]
+    pub position: Account<'info, Position>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SweepPosition<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_CLAIMS) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", owner.key().as_ref(), market.collateral_mint.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.user == owner.key(),
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", market.collateral_mint.as_ref()],
+        bump = collateral.bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = sweeper,
+        space = 8 + Referral::INIT_SPACE,
+        seeds = [b"referral", owner.key().as_ref()],
+        bump,
+    )]
+    pub referral: Account<'info, Referral>,
+
+    /// Balance of the owner's referrer in the market's mint, created if
+    /// needed; required when a referred owner won
+    #[account(
+        init_if_needed,
+        payer = sweeper,
+        space = 8 + UserAccount::INIT_SPACE,
+        seeds = [b"user_account", referral.referrer.as_ref(), market.collateral_mint.as_ref()],
+        bump,
+        constraint = referral.referrer != Pubkey::default() @ ArenaError::InvalidReferrer,
+    )]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"position", market.key().as_ref(), owner.key().as_ref()],
+        bump = position.bump,
+        constraint = position.user == owner.key() @ ArenaError::Unauthorized,
+        constraint = position.market == market.key() @ ArenaError::InvalidPosition,
+    )]
+    pub position: Account<'info, Position>,
+
+    /// CHECK: Position owner, receives the position's rent
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub sweeper: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
 
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+pub struct CloseMarket<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        close = rent_receiver,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", market.collateral_mint.as_ref()],
+        bump = collateral.bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    /// Sample buffer of a window market, closed along with it
+    #[account(
+        mut,
+        close = rent_receiver,
+        seeds = [b"samples", market.key().as_ref()],
+        bump = samples.bump,
+    )]
+    pub samples: Option<Account<'info, MarketSamples>>,
+
+    /// CHECK: Creator that paid for the market, receives its rent
+    #[account(
+        mut,
+        constraint = rent_receiver.key() == market.creator @ ArenaError::Unauthorized,
+    )]
+    pub rent_receiver: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SettleCreationBond<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_CLAIMS) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        mut,
+        seeds = [b"user_account", market.creator.as_ref(), arena_state.swtch_mint.as_ref()],
+        bump = creator_account.bump,
+    )]
+    pub creator_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", arena_state.swtch_mint.as_ref()],
+        bump = swtch_collateral.bump,
+    )]
+    pub swtch_collateral: Account<'info, CollateralConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimCreatorFee<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_CLAIMS) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"market", market.id.to_le_bytes().as_ref()],
+        bump = market.bump,
+        constraint = market.creator == creator.key() @ ArenaError::Unauthorized,
+    )]
+    pub market: Account<'info, Market>,
+
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + UserAccount::INIT_SPACE,
+        seeds = [b"user_account", creator.key().as_ref(), market.collateral_mint.as_ref()],
+        bump,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(mut)]
+    pub creator: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SweepFees<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = !arena_state.is_paused(PAUSE_WITHDRAWALS) @ ArenaError::Paused,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", collateral.mint.as_ref()],
+        bump = collateral.bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    #[account(address = collateral.mint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", collateral.mint.as_ref()],
+        bump,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = treasury.owner == arena_state.treasury @ ArenaError::InvalidTreasury,
+        constraint = treasury.mint == collateral.mint @ ArenaError::InvalidTreasury,
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AddCollateral<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + CollateralConfig::INIT_SPACE,
+        seeds = [b"collateral", mint.key().as_ref()],
+        bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = mint,
+        token::authority = arena_state,
+        token::token_program = token_program,
+        seeds = [b"vault", mint.key().as_ref()],
+        bump,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub authority: Signer<'info>,
+
+    /// Pays the rent, so a multisig or council authority need not hold SOL
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollateralEnabled<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral", collateral.mint.as_ref()],
+        bump = collateral.bump,
+    )]
+    pub collateral: Account<'info, CollateralConfig>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveFeed<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ApprovedFeed::INIT_SPACE,
+        seeds = [b"approved_feed", oracle_feed.key().as_ref()],
+        bump,
+    )]
+    pub approved_feed: Account<'info, ApprovedFeed>,
+
+    /// CHECK: only its address is recorded; the feed itself is checked
+    /// against the approval in `create_market`
+    pub oracle_feed: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+
+    /// Pays the rent, so a multisig or council authority need not hold SOL
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeed<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"approved_feed", approved_feed.feed.as_ref()],
+        bump = approved_feed.bump,
+    )]
+    pub approved_feed: Account<'info, ApprovedFeed>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveFeed<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        close = rent_receiver,
+        seeds = [b"approved_feed", approved_feed.feed.as_ref()],
+        bump = approved_feed.bump,
+    )]
+    pub approved_feed: Account<'info, ApprovedFeed>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: receives the reclaimed rent
+    #[account(mut)]
+    pub rent_receiver: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPause<'info> {
+    #[account(
+        mut,
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        seeds = [b"roles"],
+        bump = role_registry.bump,
+        constraint = arena_state.authority == authority.key()
+            || role_registry.has_role(&authority.key(), ROLE_PAUSER) @ ArenaError::Unauthorized,
+    )]
+    pub role_registry: Account<'info, RoleRegistry>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(change: AdminChange)]
+pub struct QueueChange<'info> {
+    #[account(
+        mut,
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        seeds = [b"roles"],
+        bump = role_registry.bump,
+        constraint = arena_state.authority == proposer.key()
+            || (change.is_fee() && role_registry.has_role(&proposer.key(), ROLE_FEE_ADMIN))
+            @ ArenaError::Unauthorized,
+    )]
+    pub role_registry: Account<'info, RoleRegistry>,
+
     #[account(
         init,
-        payer = authority,
-        space = 8 + ArenaState::INIT_SPACE,
-        seeds = [b"arena_state"],
+        payer = payer,
+        space = 8 + PendingChange::INIT_SPACE,
+        seeds = [b"pending_change", arena_state.total_changes.to_le_bytes().as_ref()],
         bump,
     )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    pub proposer: Signer<'info>,
+
+    /// Pays the rent, so a multisig or council authority need not hold SOL
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteChange<'info> {
+    #[account(
+        mut,
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"pending_change", pending_change.id.to_le_bytes().as_ref()],
+        bump = pending_change.bump,
+        close = payer,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    /// CHECK: Receives the rent back; must be whoever paid for the change
+    #[account(mut, address = pending_change.payer)]
+    pub payer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelChange<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"pending_change", pending_change.id.to_le_bytes().as_ref()],
+        bump = pending_change.bump,
+        close = payer,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    /// CHECK: Receives the rent back; must be whoever paid for the change
+    #[account(mut, address = pending_change.payer)]
+    pub payer: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ManageRoles<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    #[account(
+        mut,
+        seeds = [b"roles"],
+        bump = role_registry.bump,
+    )]
+    pub role_registry: Account<'info, RoleRegistry>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.pending_authority == new_authority.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    pub new_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptTreasury<'info> {
+    #[account(
+        mut,
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.pending_treasury == new_treasury.key() @ ArenaError::Unauthorized,
+    )]
+    pub arena_state: Account<'info, ArenaState>,
+
+    pub new_treasury: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateCouncil<'info> {
+    #[account(
+        seeds = [b"arena_state"],
+        bump = arena_state.bump,
+        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
+    )]
     pub arena_state: Account<'info, ArenaState>,
 
-    #[account(
-        init,
-        payer = authority,
-        token::mint = swtch_mint,
-        token::authority = arena_state,
-        seeds = [b"vault"],
-        bump,
-    )]
-    pub vault: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + AdminCouncil::INIT_SPACE,
+        seeds = [b"council"],
+        bump,
+    )]
+    pub council: Account<'info, AdminCouncil>,
+
+    pub authority: Signer<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCouncil<'info> {
+    /// Signed by the council PDA itself via `execute_council_action`
+    #[account(
+        mut,
+        seeds = [b"council"],
+        bump = council.bump,
+        constraint = council.to_account_info().is_signer @ ArenaError::Unauthorized,
+    )]
+    pub council: Account<'info, AdminCouncil>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeCouncilAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"council"],
+        bump = council.bump,
+        constraint = council.members.contains(&proposer.key()) @ ArenaError::NotCouncilMember,
+    )]
+    pub council: Account<'info, AdminCouncil>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + CouncilAction::INIT_SPACE,
+        seeds = [b"council_action", council.total_actions.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub action: Account<'info, CouncilAction>,
+
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveCouncilAction<'info> {
+    #[account(
+        seeds = [b"council"],
+        bump = council.bump,
+        constraint = council.members.contains(&member.key()) @ ArenaError::NotCouncilMember,
+    )]
+    pub council: Account<'info, AdminCouncil>,
+
+    #[account(
+        mut,
+        seeds = [b"council_action", action.id.to_le_bytes().as_ref()],
+        bump = action.bump,
+    )]
+    pub action: Account<'info, CouncilAction>,
+
+    pub member: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteCouncilAction<'info> {
+    /// Not `mut`: the action may itself update the council, and writing this
+    /// copy back afterwards would undo it
+    #[account(
+        seeds = [b"council"],
+        bump = council.bump,
+    )]
+    pub council: Account<'info, AdminCouncil>,
+
+    #[account(
+        mut,
+        seeds = [b"council_action", action.id.to_le_bytes().as_ref()],
+        bump = action.bump,
+        close = proposer,
+    )]
+    pub action: Account<'info, CouncilAction>,
+
+    /// CHECK: Receives the rent back; must be whoever proposed the action
+    #[account(mut, address = action.proposer)]
+    pub proposer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelCouncilAction<'info> {
+    #[account(
+        mut,
+        seeds = [b"council_action", action.id.to_le_bytes().as_ref()],
+        bump = action.bump,
+        close = proposer,
+        constraint = action.proposer == proposer.key() @ ArenaError::Unauthorized,
+    )]
+    pub action: Account<'info, CouncilAction>,
+
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+}
+
+// ============================================================================
+// STATE
+// ============================================================================
+
+#[account]
+#[derive(InitSpace)]
+pub struct ArenaState {
+    pub authority: Pubkey,
+    /// Proposed authority awaiting `accept_authority` (default if none)
+    pub pending_authority: Pubkey,
+    /// Wallet owning the fee token account for each collateral mint
+    pub treasury: Pubkey,
+    /// Proposed treasury awaiting `accept_treasury` (default if none)
+    pub pending_treasury: Pubkey,
+    /// Mint bonds are posted in
+    pub swtch_mint: Pubkey,
+    pub total_markets: u64,
+    pub protocol_fee_bps: u16,
+    /// Fee kept when stake is taken out with `reduce_position`
+    pub exit_fee_bps: u16,
+    /// Default oracle health checks for new markets
+    pub oracle_policy: OraclePolicy,
+    /// Time after resolution during which the outcome can be challenged
+    pub dispute_period_secs: i64,
+    /// Bond a challenger posts from their arena balance
+    pub dispute_bond: u64,
+    /// Time after claims open before unclaimed positions can be swept
+    pub claim_expiry_secs: i64,
+    /// Bitmask of paused `PAUSE_*` scopes
+    pub paused: u8,
+    /// Default bet limits for new markets
+    pub bet_limits: BetLimits,
+    /// Cap on a user's total stake across unsettled positions in one mint
+    /// (0 = no cap)
+    pub max_open_exposure: u64,
+    /// Anyone may create markets, posting `creation_bond`
+    pub open_creation: bool,
+    /// $SWTCH bond community creators post per market
+    pub creation_bond: u64,
+    /// Share of a community market's protocol fee paid to its creator
+    pub creator_fee_bps: u16,
+    /// Share of the protocol fee on a referred user's winnings paid to
+    /// their referrer
+    pub referral_fee_bps: u16,
+    /// Delay between queueing and executing an `AdminChange`
+    pub timelock_delay_secs: i64,
+    /// Number of admin changes ever queued; seeds the next `PendingChange`
+    pub total_changes: u64,
+    pub bump: u8,
+}
+
+impl ArenaState {
+    pub fn is_paused(&self, scope: u8) -> bool {
+        self.paused & scope != 0
+    }
+}
+
+/// Keys granted roles by the authority
+#[account]
+#[derive(InitSpace)]
+pub struct RoleRegistry {
+    #[max_len(MAX_ROLE_MEMBERS)]
+    pub members: Vec<RoleMember>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct RoleMember {
+    pub member: Pubkey,
+    /// Bitmask of `ROLE_*` roles
+    pub roles: u8,
+}
+
+impl RoleRegistry {
+    pub fn has_role(&self, member: &Pubkey, role: u8) -> bool {
+        self.members
+            .iter()
+            .any(|entry| entry.member == *member && entry.roles & role != 0)
+    }
+
+    /// Add `roles` to `member`, returning all roles it now holds
+    pub fn grant(&mut self, member: Pubkey, roles: u8) -> Result<u8> {
+        if let Some(entry) = self.members.iter_mut().find(|entry| entry.member == member) {
+            entry.roles |= roles;
+            return Ok(entry.roles);
+        }
+
+        require!(self.members.len() < MAX_ROLE_MEMBERS, ArenaError::RoleRegistryFull);
+        self.members.push(RoleMember { member, roles });
+        Ok(roles)
+    }
+
+    /// Remove `roles` from `member`, returning the roles it still holds;
+    /// keys left without roles are dropped from the registry
+    pub fn revoke(&mut self, member: Pubkey, roles: u8) -> Result<u8> {
+        let index = self
+            .members
+            .iter()
+            .position(|entry| entry.member == member)
+            .ok_or(ArenaError::RoleNotHeld)?;
+
+        let remaining = self.members[index].roles & !roles;
+        if remaining == 0 {
+            self.members.swap_remove(index);
+        } else {
+            self.members[index].roles = remaining;
+        }
+        Ok(remaining)
+    }
+}
+
+/// Built-in M-of-N admin council; the `[b"council"]` PDA can be made the
+/// arena authority and signs approved `CouncilAction`s
+#[account]
+#[derive(InitSpace)]
+pub struct AdminCouncil {
+    #[max_len(MAX_COUNCIL_MEMBERS)]
+    pub members: Vec<Pubkey>,
+    /// Approvals needed to execute an action
+    pub threshold: u8,
+    /// Number of actions ever proposed; seeds the next `CouncilAction`
+    pub total_actions: u64,
+    pub bump: u8,
+}
+
+impl AdminCouncil {
+    pub fn validate(members: &[Pubkey], threshold: u8) -> Result<()> {
+        require!(
+            !members.is_empty()
+                && members.len() <= MAX_COUNCIL_MEMBERS
+                && threshold > 0
+                && threshold as usize <= members.len(),
+            ArenaError::InvalidCouncil
+        );
+        for (i, member) in members.iter().enumerate() {
+            require!(
+                *member != Pubkey::default() && !members[..i].contains(member),
+                ArenaError::InvalidCouncil
+            );
+        }
+        Ok(())
+    }
+
+    /// Approvals on `action` from keys that are still members
+    pub fn approvals_for(&self, action: &CouncilAction) -> u8 {
+        action.approvals.iter()
+            .filter(|approver| self.members.contains(approver))
+            .count() as u8
+    }
+}
+
+/// An arena instruction proposed for the council to sign
+#[account]
+#[derive(InitSpace)]
+pub struct CouncilAction {
+    pub id: u64,
+    pub proposer: Pubkey,
+    #[max_len(MAX_COUNCIL_ACTION_ACCOUNTS)]
+    pub accounts: Vec<CouncilAccountMeta>,
+    #[max_len(MAX_COUNCIL_ACTION_DATA_LEN)]
+    pub data: Vec<u8>,
+    #[max_len(MAX_COUNCIL_MEMBERS)]
+    pub approvals: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct CouncilAccountMeta {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+/// An admin change waiting out the timelock
+#[account]
+#[derive(InitSpace)]
+pub struct PendingChange {
+    pub id: u64,
+    pub proposer: Pubkey,
+    /// Paid the rent, which is returned when the change is executed or
+    /// cancelled
+    pub payer: Pubkey,
+    pub change: AdminChange,
+    pub executable_at: i64,
+    pub bump: u8,
+}
+
+/// An allowlisted collateral mint; its vault is the `[b"vault", mint]` PDA
+#[account]
+#[derive(InitSpace)]
+pub struct CollateralConfig {
+    pub mint: Pubkey,
+    /// Accepted for new deposits and markets
+    pub enabled: bool,
+    pub total_volume: u64,
+    /// Fees escrowed in this mint and not yet swept to the treasury
+    pub accrued_fees: u64,
+    pub bump: u8,
+}
+
+/// An oracle feed markets may be listed on, and what it must satisfy
+#[account]
+#[derive(InitSpace)]
+pub struct ApprovedFeed {
+    pub feed: Pubkey,
+    pub oracle_kind: OracleKind,
+    /// Queue whose oracles must serve the feed
+    pub oracle_queue: Pubkey,
+    /// Floor on `OraclePolicy::min_oracle_results` for markets on this feed
+    pub min_oracle_results: u32,
+    #[max_len(32)]
+    pub label: String,
+    #[max_len(50)]
+    pub category: String,
+    /// Reject the feed while its authority can still reconfigure it
+    pub require_locked: bool,
+    pub bump: u8,
+}
+
+impl ApprovedFeed {
+    pub fn apply(&mut self, params: ApprovedFeedParams) {
+        self.oracle_kind = params.oracle_kind;
+        self.oracle_queue = params.oracle_queue;
+        self.min_oracle_results = params.min_oracle_results;
+        self.label = params.label;
+        self.category = params.category;
+        self.require_locked = params.require_locked;
+    }
+
+    /// Checks a live reading of the feed against the approval
+    pub fn check(&self, oracle_kind: OracleKind, reading: &OracleReading) -> Result<()> {
+        require!(self.oracle_kind == oracle_kind, ArenaError::InvalidOracle);
+        require_keys_eq!(reading.queue, self.oracle_queue, ArenaError::FeedQueueMismatch);
+        require!(
+            !self.require_locked || reading.locked,
+            ArenaError::FeedNotLocked
+        );
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Market {
+    pub id: u64,
+    /// Listed the market and paid its rent
+    pub creator: Pubkey,
+    /// Mint every stake and payout in this market is denominated in
+    pub collateral_mint: Pubkey,
+    pub oracle_feed: Pubkey,
+    pub oracle_kind: OracleKind,
+    #[max_len(200)]
+    pub description: String,
+    #[max_len(50)]
+    pub category: String,
+    pub market_type: MarketType,
+    pub start_value: i128,
+    /// Value the oracle result is compared against at resolution.
+    /// Equal to `start_value` for UP/DOWN markets.
+    pub strike_value: i128,
+    pub threshold_direction: ThresholdDirection,
+    /// Void instead of resolving DOWN when the value equals the strike
+    pub void_on_tie: bool,
+    /// Feed health checks applied at creation and resolution
+    pub oracle_policy: OraclePolicy,
+    pub bet_limits: BetLimits,
+    pub settlement: SettlementConfig,
+    /// Outcome names for categorical markets (empty otherwise)
+    #[max_len(MAX_OUTCOMES, MAX_OUTCOME_LABEL_LEN)]
+    pub outcome_labels: Vec<String>,
+    /// Per-outcome pools for categorical markets (empty otherwise)
+    #[max_len(MAX_OUTCOMES)]
+    pub outcome_pools: Vec<u64>,
+    pub categorical_resolution: CategoricalResolution,
+    /// Ascending boundaries between adjacent outcomes when resolving by
+    /// value ranges: outcome `i` wins if `range_bounds[i - 1] <= value < range_bounds[i]`
+    #[max_len(MAX_OUTCOMES - 1)]
+    pub range_bounds: Vec<i128>,
+    /// Range a scalar market settles across (LONG pays 0 at `lower_bound`
+    /// and the whole pool at `upper_bound`)
+    pub lower_bound: i128,
+    pub upper_bound: i128,
+    /// Betting closes at this time
+    pub lock_time: i64,
+    pub resolution_time: i64,
+    /// Oracle value read at resolution
+    pub end_value: i128,
+    pub total_up_pool: u64,
+    pub total_down_pool: u64,
+    pub resolved: bool,
+    pub outcome: Option<MarketOutcome>,
+    /// Protocol fee for this market, escrowed once the outcome is final
+    pub fee_collected: u64,
+    /// $SWTCH bond held until `settle_creation_bond` (0 for trusted creators)
+    pub creation_bond: u64,
+    /// Creator's share of `fee_collected`, fixed at creation
+    pub creator_fee_bps: u16,
+    /// Creator's share held for `claim_creator_fee`
+    pub creator_fee: u64,
+    /// Referrers' share of the protocol fee, fixed at creation
+    pub referral_fee_bps: u16,
+    /// Protocol fee held back for referrers, split pro rata between winners
+    pub referral_reserve: u64,
+    /// Part of `referral_reserve` settled so far; `close_market` moves the
+    /// rounding dust left over into the protocol fees
+    pub referral_paid: u64,
+    pub status: MarketStatus,
+    /// End of the dispute period; the market can be finalized after this
+    /// unless disputed
+    pub dispute_deadline: i64,
+    pub challenger: Pubkey,
+    /// Bond held while the market is disputed
+    pub challenge_bond: u64,
+    /// Outcome the challenger argued for
+    pub proposed_outcome: Option<MarketOutcome>,
+    pub cancel_reason: Option<CancelReason>,
+    /// Positions not yet claimed or swept; the market can be closed at 0
+    pub open_positions: u32,
+    pub bump: u8,
+}
+
+impl Market {
+    /// Maps the oracle value at resolution to the winning outcome, voiding
+    /// the market if nobody backed the winning side or (when `void_on_tie`
+    /// is set) the value landed exactly on the start value or strike
+    pub fn outcome_for(&self, end_value: i128) -> Result<MarketOutcome> {
+        let outcome = match self.market_type {
+            MarketType::UpDown | MarketType::Threshold => {
+                let up_wins = match (self.market_type, self.threshold_direction) {
+                    (MarketType::UpDown, _) => end_value > self.start_value,
+                    (_, ThresholdDirection::Above) => end_value > self.strike_value,
+                    (_, ThresholdDirection::Below) => end_value < self.strike_value,
+                    (_, ThresholdDirection::AtOrAbove) => end_value >= self.strike_value,
+                };
+                let is_tie = end_value == self.strike_value
+                    && self.threshold_direction != ThresholdDirection::AtOrAbove;
+                if is_tie && self.void_on_tie {
+                    return Ok(MarketOutcome::Void);
+                }
+                if up_wins { MarketOutcome::Up } else { MarketOutcome::Down }
+            }
+            MarketType::Categorical => {
+                let index = match self.categorical_resolution {
+                    CategoricalResolution::Index => {
+                        // An index with no outcome can never be settled, so
+                        // refund everyone rather than block resolution
+                        if end_value < 0 || end_value >= self.outcome_pools.len() as i128 {
+                            return Ok(MarketOutcome::Void);
+                        }
+                        end_value as u8
+                    }
+                    CategoricalResolution::Ranges => self
+                        .range_bounds
+                        .iter()
+                        .take_while(|bound| end_value >= **bound)
+                        .count() as u8,
+                };
+                MarketOutcome::Outcome { index }
+            }
+            MarketType::Scalar => {
+                let range = self
+                    .upper_bound
+                    .checked_sub(self.lower_bound)
+                    .ok_or(ArenaError::Overflow)?;
+                let clamped = end_value.clamp(self.lower_bound, self.upper_bound);
+                let long_share = clamped
+                    .checked_sub(self.lower_bound)
+                    .and_then(|offset| offset.checked_mul(SCALAR_SHARE_PRECISION as i128))
+                    .ok_or(ArenaError::Overflow)?
+                    .checked_div(range)
+                    .ok_or(ArenaError::DivisionByZero)? as u64;
+                MarketOutcome::Scalar { long_share }
+            }
+        };
 
-    pub swtch_mint: Account<'info, Mint>,
+        Ok(self.refund_if_unbacked(outcome))
+    }
 
-    /// CHECK: Treasury wallet for fee collection
-    pub treasury: UncheckedAccount<'info>,
+    /// Voids `outcome` if nobody backed the winning side, refunding everyone
+    /// rather than stranding the pool in the vault
+    pub fn refund_if_unbacked(&self, outcome: MarketOutcome) -> MarketOutcome {
+        let winning_side_empty = match outcome {
+            MarketOutcome::Up => self.total_up_pool == 0,
+            MarketOutcome::Down => self.total_down_pool == 0,
+            MarketOutcome::Outcome { index } => self.outcome_pools[index as usize] == 0,
+            MarketOutcome::Scalar { long_share } => {
+                (long_share > 0 && self.total_up_pool == 0)
+                    || (long_share < SCALAR_SHARE_PRECISION && self.total_down_pool == 0)
+            }
+            MarketOutcome::Void => false,
+        };
+        if winning_side_empty {
+            MarketOutcome::Void
+        } else {
+            outcome
+        }
+    }
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    /// Checks that positions can be paid out: the market is finalized or
+    /// cancelled, so its fee and referral reserve are already escrowed
+    pub fn check_claimable(&self) -> Result<()> {
+        require!(self.resolved, ArenaError::MarketNotResolved);
+        require!(
+            self.status != MarketStatus::Disputed,
+            ArenaError::MarketDisputed
+        );
+        require!(
+            matches!(self.status, MarketStatus::Finalized | MarketStatus::Cancelled),
+            ArenaError::MarketNotFinal
+        );
+        Ok(())
+    }
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
+    /// Checks that `outcome` is one this market can settle on
+    pub fn check_outcome(&self, outcome: MarketOutcome) -> Result<()> {
+        let valid = match (self.market_type, outcome) {
+            (_, MarketOutcome::Void) => true,
+            (MarketType::UpDown | MarketType::Threshold, MarketOutcome::Up | MarketOutcome::Down) => true,
+            (MarketType::Categorical, MarketOutcome::Outcome { index }) => {
+                (index as usize) < self.outcome_pools.len()
+            }
+            (MarketType::Scalar, MarketOutcome::Scalar { long_share }) => {
+                long_share <= SCALAR_SHARE_PRECISION
+            }
+            _ => false,
+        };
+        require!(valid, ArenaError::InvalidOutcome);
+        Ok(())
+    }
+
+    pub fn total_pool(&self) -> Result<u64> {
+        self.outcome_pools
+            .iter()
+            .try_fold(self.total_up_pool, |total, pool| total.checked_add(*pool))
+            .and_then(|total| total.checked_add(self.total_down_pool))
+            .ok_or(error!(ArenaError::Overflow))
+    }
+
+    /// Splits `pool_after_fee` between the position's side and everyone
+    /// else, then pays the position pro rata within its side:
+    /// payout = (user_wager / side_pool) * side_share_of_pool
+    pub fn payout_for(&self, position: &Position, pool_after_fee: u64) -> Result<u64> {
+        let outcome = self.outcome.ok_or(ArenaError::MarketNotResolved)?;
+        let pool = pool_after_fee as u128;
+
+        let payout = match outcome {
+            MarketOutcome::Up => pro_rata(position.up_amount, pool, self.total_up_pool)?,
+            MarketOutcome::Down => pro_rata(position.down_amount, pool, self.total_down_pool)?,
+            MarketOutcome::Outcome { index } => pro_rata(
+                position.outcome_amounts[index as usize],
+                pool,
+                self.outcome_pools[index as usize],
+            )?,
+            MarketOutcome::Void => 0,
+            // A position holding both sides is paid from each
+            MarketOutcome::Scalar { long_share } => {
+                let long_pool = pool
+                    .checked_mul(long_share as u128)
+                    .ok_or(ArenaError::Overflow)?
+                    / SCALAR_SHARE_PRECISION as u128;
+                pro_rata(position.up_amount, long_pool, self.total_up_pool)?
+                    .checked_add(pro_rata(position.down_amount, pool - long_pool, self.total_down_pool)?)
+                    .ok_or(ArenaError::Overflow)?
+            }
+        };
+
+        Ok(payout as u64)
+    }
+
+    /// Pool holding the stakes on `side`, if this market takes bets on it
+    pub fn side_pool_mut(&mut self, side: BetSide) -> Result<&mut u64> {
+        match side {
+            BetSide::Up | BetSide::Down => {
+                require!(
+                    self.market_type != MarketType::Categorical,
+                    ArenaError::InvalidMarketType
+                );
+                Ok(if side == BetSide::Up {
+                    &mut self.total_up_pool
+                } else {
+                    &mut self.total_down_pool
+                })
+            }
+            BetSide::Outcome { index } => {
+                require!(
+                    self.market_type == MarketType::Categorical,
+                    ArenaError::InvalidMarketType
+                );
+                self.outcome_pools
+                    .get_mut(index as usize)
+                    .ok_or(error!(ArenaError::InvalidOutcomeIndex))
+            }
+        }
+    }
 }
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(
-        seeds = [b"arena_state"],
-        bump = arena_state.bump,
-    )]
-    pub arena_state: Account<'info, ArenaState>,
+#[account]
+#[derive(InitSpace)]
+pub struct MarketSamples {
+    pub market: Pubkey,
+    /// Readings in strictly increasing timestamp order
+    #[max_len(MAX_SETTLEMENT_SAMPLES)]
+    pub samples: Vec<OracleSample>,
+    pub bump: u8,
+}
 
-    #[account(
-        mut,
-        seeds = [b"vault"],
-        bump,
-    )]
-    pub vault: Account<'info, TokenAccount>,
+impl MarketSamples {
+    /// Weights each sample by how long it stood before the next one (the
+    /// last sample stands until `end`)
+    pub fn time_weighted_average(&self, end: i64) -> Result<i128> {
+        let mut weighted_sum: i128 = 0;
+        let mut total_weight: i128 = 0;
+        for (i, sample) in self.samples.iter().enumerate() {
+            let next = self.samples.get(i + 1).map_or(end, |s| s.timestamp);
+            let weight = next.saturating_sub(sample.timestamp).max(0) as i128;
+            weighted_sum = sample.value
+                .checked_mul(weight)
+                .and_then(|v| weighted_sum.checked_add(v))
+                .ok_or(ArenaError::Overflow)?;
+            total_weight += weight;
+        }
 
-    #[account(
-        init_if_needed,
-        payer = user,
-        space = 8 + UserAccount::INIT_SPACE,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump,
-    )]
-    pub user_account: Account<'info, UserAccount>,
+        // Every sample landed at `end`; fall back to the plain mean
+        if total_weight == 0 {
+            let sum = self.samples.iter()
+                .try_fold(0i128, |sum, s| sum.checked_add(s.value))
+                .ok_or(ArenaError::Overflow)?;
+            return Ok(sum / self.samples.len() as i128);
+        }
 
-    #[account(
-        mut,
-        constraint = user_token_account.owner == user.key(),
-        constraint = user_token_account.mint == arena_state.swtch_mint,
-    )]
-    pub user_token_account: Account<'info, TokenAccount>,
+        Ok(weighted_sum / total_weight)
+    }
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+    /// Lower median of the sample values
+    pub fn median(&self) -> i128 {
+        let mut values: Vec<i128> = self.samples.iter().map(|s| s.value).collect();
+        values.sort_unstable();
+        values[(values.len() - 1) / 2]
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Position {
+    pub user: Pubkey,
+    pub market: Pubkey,
+    /// Stake on UP (LONG for scalar markets)
+    pub up_amount: u64,
+    /// Stake on DOWN (SHORT for scalar markets)
+    pub down_amount: u64,
+    /// Stake on each outcome of a categorical market
+    pub outcome_amounts: [u64; MAX_OUTCOMES],
+    /// Total stake across all sides
+    pub amount: u64,
+    pub claimed: bool,
+    pub bump: u8,
+}
+
+impl Position {
+    pub fn is_winner(&self, outcome: MarketOutcome) -> bool {
+        match outcome {
+            MarketOutcome::Up => self.up_amount > 0,
+            MarketOutcome::Down => self.down_amount > 0,
+            MarketOutcome::Outcome { index } => self.outcome_amounts[index as usize] > 0,
+            // LONG and SHORT both win whatever part of the range they hold
+            MarketOutcome::Scalar { long_share } => {
+                (self.up_amount > 0 && long_share > 0)
+                    || (self.down_amount > 0 && long_share < SCALAR_SHARE_PRECISION)
+            }
+            MarketOutcome::Void => false,
+        }
+    }
+
+    pub fn stake(&self, side: BetSide) -> u64 {
+        match side {
+            BetSide::Up => self.up_amount,
+            BetSide::Down => self.down_amount,
+            BetSide::Outcome { index } => {
+                self.outcome_amounts.get(index as usize).copied().unwrap_or(0)
+            }
+        }
+    }
+
+    fn stake_mut(&mut self, side: BetSide) -> Result<&mut u64> {
+        match side {
+            BetSide::Up => Ok(&mut self.up_amount),
+            BetSide::Down => Ok(&mut self.down_amount),
+            BetSide::Outcome { index } => self.outcome_amounts
+                .get_mut(index as usize)
+                .ok_or(error!(ArenaError::InvalidOutcomeIndex)),
+        }
+    }
+
+    pub fn add_stake(&mut self, side: BetSide, amount: u64) -> Result<()> {
+        let stake = self.stake_mut(side)?;
+        *stake = stake.checked_add(amount)
+            .ok_or(ArenaError::Overflow)?;
+        self.amount = self.amount.checked_add(amount)
+            .ok_or(ArenaError::Overflow)?;
+        Ok(())
+    }
+
+    pub fn remove_stake(&mut self, side: BetSide, amount: u64) -> Result<()> {
+        let stake = self.stake_mut(side)?;
+        *stake = stake.checked_sub(amount)
+            .ok_or(ArenaError::InsufficientStake)?;
+        self.amount = self.amount.checked_sub(amount)
+            .ok_or(ArenaError::Underflow)?;
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct UserAccount {
+    pub user: Pubkey,
+    /// Collateral mint this balance is held in
+    pub mint: Pubkey,
+    pub balance: u64,
+    pub total_wagered: u64,
+    /// Stake in positions not yet claimed or swept, on markets that have
+    /// not reached their resolution time
+    pub open_exposure: u64,
+    /// Latest resolution time of the markets counted in `open_exposure`
+    pub exposure_expires_at: i64,
+    /// Stakes on markets resolving at or before this time are no longer
+    /// counted in `open_exposure`
+    pub exposure_released_at: i64,
+    pub total_won: u64,
+    pub wins: u32,
+    pub losses: u32,
+    pub current_streak: u32,
+    pub best_streak: u32,
+    /// Fee rebates earned from referred users' winnings
+    pub referral_earnings: u64,
+    pub bump: u8,
+}
+
+impl UserAccount {
+    /// Records the owner of a balance created on demand
+    pub fn init_if_new(&mut self, user: Pubkey, mint: Pubkey, bump: u8) {
+        if self.user == Pubkey::default() {
+            self.user = user;
+            self.mint = mint;
+            self.bump = bump;
+        }
+    }
+}
+
+/// A user's referrer, shared by their balances in every mint
+#[account]
+#[derive(InitSpace)]
+pub struct Referral {
+    pub user: Pubkey,
+    /// Named by the user's first deposit (default if none)
+    pub referrer: Pubkey,
+    /// The user has made their first deposit, fixing `referrer`
+    pub deposited: bool,
+    /// Users who named this user as their referrer
+    pub referral_count: u32,
+    pub bump: u8,
+}
+
+impl Referral {
+    /// Records the owner of a `Referral` created on demand
+    pub fn init_if_new(&mut self, user: Pubkey, bump: u8) {
+        if self.user == Pubkey::default() {
+            self.user = user;
+            self.bump = bump;
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum MarketType {
+    /// UP wins if the oracle ends above its value at creation
+    UpDown,
+    /// UP ("YES") wins if the oracle satisfies the strike condition
+    Threshold,
+    /// One of up to `MAX_OUTCOMES` named outcomes wins
+    Categorical,
+    /// LONG (UP) and SHORT (DOWN) split the pool linearly between bounds
+    Scalar,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum CategoricalResolution {
+    /// Oracle reports the winning outcome index directly
+    Index,
+    /// Oracle value is bucketed by `Market::range_bounds`
+    Ranges,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum MarketOutcome {
+    Up,
+    Down,
+    Outcome { index: u8 },
+    /// LONG's share of the pool, out of `SCALAR_SHARE_PRECISION`
+    Scalar { long_share: u64 },
+    /// Every position is refunded its stake without a protocol fee
+    Void,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum MarketStatus {
+    /// Accepting bets until `lock_time`
+    Open,
+    /// Betting closed, waiting for `resolution_time`
+    Locked,
+    /// Outcome set; can be challenged until `dispute_deadline`
+    Resolved,
+    /// Outcome challenged and awaiting `settle_dispute`
+    Disputed,
+    /// Outcome final and fee escrowed
+    Finalized,
+    /// Aborted by the authority or an emergency resolver; every stake is refunded
+    Cancelled,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum CancelReason {
+    /// Description, category or parameters were listed incorrectly
+    Mislisted,
+    /// The oracle feed was deprecated or stopped updating
+    OracleDeprecated,
+    /// A bug affected the market
+    ProgramBug,
+    Other,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum BetSide {
+    /// UP, YES or LONG
+    Up,
+    /// DOWN, NO or SHORT
+    Down,
+    /// One outcome of a categorical market
+    Outcome { index: u8 },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ThresholdDirection {
+    Above,
+    Below,
+    AtOrAbove,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum OracleKind {
+    /// Legacy Switchboard V2 `AggregatorAccountData`
+    AggregatorV2,
+    /// Switchboard On-Demand `PullFeedAccountData`
+    PullFeed,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct OraclePolicy {
+    /// Maximum age of the oracle round, in seconds
+    pub max_staleness_secs: i64,
+    /// Maximum standard deviation across oracle responses, in the feed's
+    /// native representation (0 disables the check)
+    pub max_std_deviation: i128,
+    /// Minimum number of oracles that must have responded
+    pub min_oracle_results: u32,
+    /// How long after `resolution_time` an oracle round may arrive before
+    /// the market voids instead
+    pub resolution_grace_secs: i64,
+}
+
+impl OraclePolicy {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.max_staleness_secs > 0
+                && self.max_std_deviation >= 0
+                && self.min_oracle_results > 0
+                && self.resolution_grace_secs > 0,
+            ArenaError::InvalidOraclePolicy
+        );
+        Ok(())
+    }
+}
+
+/// Stake limits, in base units of the market's collateral mint
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct BetLimits {
+    pub min_bet_amount: u64,
+    pub max_bet_amount: u64,
+    /// Cap on one user's total stake in a market (0 = no cap)
+    pub max_position_amount: u64,
+}
+
+impl BetLimits {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.min_bet_amount > 0
+                && self.min_bet_amount <= self.max_bet_amount
+                && (self.max_position_amount == 0
+                    || self.max_position_amount >= self.min_bet_amount),
+            ArenaError::InvalidBetLimits
+        );
+        Ok(())
+    }
+
+    pub fn check_bet(&self, amount: u64) -> Result<()> {
+        require!(amount >= self.min_bet_amount, ArenaError::BetTooSmall);
+        require!(amount <= self.max_bet_amount, ArenaError::BetTooLarge);
+        Ok(())
+    }
+
+    /// `total` is the user's whole stake in the market after the bet
+    pub fn check_position(&self, total: u64) -> Result<()> {
+        require!(
+            self.max_position_amount == 0 || total <= self.max_position_amount,
+            ArenaError::PositionLimitExceeded
+        );
+        Ok(())
+    }
+}
+
+/// Admin parameter changes that must wait out the timelock
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum AdminChange {
+    ProtocolFee { fee_bps: u16 },
+    ExitFee { fee_bps: u16 },
+    /// Proposes the treasury, which must still `accept_treasury`
+    Treasury { treasury: Pubkey },
+    /// Default bet limits for new markets and the open exposure cap
+    BetLimits { bet_limits: BetLimits, max_open_exposure: u64 },
+    OraclePolicy { oracle_policy: OraclePolicy },
+    TimelockDelay { delay_secs: i64 },
+    ReferralFee { fee_bps: u16 },
+    DisputeConfig { dispute_period_secs: i64, dispute_bond: u64 },
+    ClaimExpiry { claim_expiry_secs: i64 },
+    /// Open market creation, the creator bond and the creator fee share
+    CreationConfig { open_creation: bool, creation_bond: u64, creator_fee_bps: u16 },
+}
+
+impl AdminChange {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            AdminChange::ProtocolFee { fee_bps } | AdminChange::ExitFee { fee_bps } => {
+                require!(*fee_bps <= 1000, ArenaError::InvalidFeePercentage); // Max 10%
+            }
+            AdminChange::Treasury { treasury } => {
+                require!(*treasury != Pubkey::default(), ArenaError::InvalidTreasury);
+            }
+            AdminChange::BetLimits { bet_limits, .. } => bet_limits.validate()?,
+            AdminChange::OraclePolicy { oracle_policy } => oracle_policy.validate()?,
+            AdminChange::TimelockDelay { delay_secs } => {
+                require!(
+                    (MIN_TIMELOCK_DELAY_SECS..=MAX_TIMELOCK_DELAY_SECS).contains(delay_secs),
+                    ArenaError::InvalidTimelockDelay
+                );
+            }
+            AdminChange::ReferralFee { fee_bps } => {
+                require!(*fee_bps <= MAX_REFERRAL_FEE_BPS, ArenaError::InvalidFeePercentage);
+            }
+            AdminChange::DisputeConfig { dispute_period_secs, dispute_bond } => {
+                require!(
+                    (0..=MAX_DISPUTE_PERIOD_SECS).contains(dispute_period_secs) && *dispute_bond > 0,
+                    ArenaError::InvalidDisputeConfig
+                );
+            }
+            AdminChange::ClaimExpiry { claim_expiry_secs } => {
+                require!(*claim_expiry_secs > 0, ArenaError::InvalidClaimExpiry);
+            }
+            AdminChange::CreationConfig { creator_fee_bps, .. } => {
+                require!(
+                    *creator_fee_bps <= MAX_CREATOR_FEE_BPS,
+                    ArenaError::InvalidFeePercentage
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Fee changes may also be queued by fee admins
+    pub fn is_fee(&self) -> bool {
+        matches!(
+            self,
+            AdminChange::ProtocolFee { .. }
+                | AdminChange::ExitFee { .. }
+                | AdminChange::ReferralFee { .. }
+        )
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum SettlementMode {
+    /// Single oracle read when `resolve_market` is called
+    Spot,
+    /// Time-weighted average of the samples in the settlement window
+    Twap,
+    /// Median of the samples in the settlement window
+    Median,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub struct SettlementConfig {
+    pub mode: SettlementMode,
+    /// Length of the sampling window ending at `resolution_time`
+    pub window_secs: i64,
+    /// Samples required before the market can resolve
+    pub min_samples: u16,
+}
+
+impl SettlementConfig {
+    pub fn validate(&self, now: i64, resolution_time: i64) -> Result<()> {
+        if self.mode == SettlementMode::Spot {
+            return Ok(());
+        }
+        require!(
+            self.window_secs > 0
+                && self.window_secs <= resolution_time.saturating_sub(now)
+                && self.min_samples > 0
+                && self.min_samples as usize <= MAX_SETTLEMENT_SAMPLES,
+            ArenaError::InvalidSettlementConfig
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct OracleSample {
+    pub timestamp: i64,
+    pub value: i128,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum MarketKind {
+    UpDown,
+    Threshold {
+        /// Strike in the oracle's native representation
+        strike_value: i128,
+        direction: ThresholdDirection,
+    },
+    Categorical {
+        labels: Vec<String>,
+        resolution: CategoricalResolution,
+        /// Required for `CategoricalResolution::Ranges`, empty otherwise
+        range_bounds: Vec<i128>,
+    },
+    Scalar {
+        lower_bound: i128,
+        upper_bound: i128,
+    },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CreateMarketParams {
+    pub oracle_feed: Pubkey,
+    pub description: String,
+    pub category: String,
+    /// Betting closes at this time (at or before `resolution_time`)
+    pub lock_time: i64,
+    pub resolution_time: i64,
+    pub kind: MarketKind,
+    /// Refund UP/DOWN and threshold markets that end exactly on the strike
+    pub void_on_tie: bool,
+    pub oracle_kind: OracleKind,
+    /// Overrides `ArenaState::oracle_policy` for this market
+    pub oracle_policy: Option<OraclePolicy>,
+    /// Overrides `ArenaState::bet_limits` for this market, e.g. for a mint
+    /// with different decimals
+    pub bet_limits: Option<BetLimits>,
+    pub settlement: SettlementConfig,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ApprovedFeedParams {
+    pub oracle_kind: OracleKind,
+    pub oracle_queue: Pubkey,
+    pub min_oracle_results: u32,
+    pub label: String,
+    pub category: String,
+    pub require_locked: bool,
+}
+
+impl ApprovedFeedParams {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.min_oracle_results > 0 && self.label.len() <= 32,
+            ArenaError::InvalidFeedApproval
+        );
+        require!(self.category.len() <= 50, ArenaError::CategoryTooLong);
+        Ok(())
+    }
+}
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+#[event]
+pub struct ArenaInitialized {
+    pub authority: Pubkey,
+    pub treasury: Pubkey,
+    pub protocol_fee_bps: u16,
+}
+
+#[event]
+pub struct Deposited {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub new_balance: u64,
+}
+
+#[event]
+pub struct ReferralRegistered {
+    pub user: Pubkey,
+    pub referrer: Pubkey,
+}
 
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct ReferralRewarded {
+    pub referrer: Pubkey,
+    /// Referred user whose winnings the rebate came from
+    pub user: Pubkey,
+    pub market: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
 }
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(
-        seeds = [b"arena_state"],
-        bump = arena_state.bump,
-    )]
-    pub arena_state: Account<'info, ArenaState>,
+#[event]
+pub struct Withdrawn {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub new_balance: u64,
+}
 
-    #[account(
-        mut,
-        seeds = [b"vault"],
-        bump,
-    )]
-    pub vault: Account<'info, TokenAccount>,
+#[event]
+pub struct MarketCreated {
+    pub market_id: u64,
+    pub collateral_mint: Pubkey,
+    pub oracle_feed: Pubkey,
+    pub oracle_kind: OracleKind,
+    pub description: String,
+    pub category: String,
+    pub market_type: MarketType,
+    pub start_value: i128,
+    pub strike_value: i128,
+    pub threshold_direction: ThresholdDirection,
+    pub outcome_labels: Vec<String>,
+    pub lock_time: i64,
+    pub resolution_time: i64,
+    pub creator: Pubkey,
+    pub creation_bond: u64,
+}
 
-    #[account(
-        mut,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump = user_account.bump,
-        constraint = user_account.user == user.key(),
-    )]
-    pub user_account: Account<'info, UserAccount>,
+#[event]
+pub struct BetPlaced {
+    pub user: Pubkey,
+    pub market: Pubkey,
+    pub prediction: bool,
+    pub amount: u64,
+    pub total_up_pool: u64,
+    pub total_down_pool: u64,
+}
 
-    #[account(
-        mut,
-        constraint = user_token_account.owner == user.key(),
-        constraint = user_token_account.mint == arena_state.swtch_mint,
-    )]
-    pub user_token_account: Account<'info, TokenAccount>,
+#[event]
+pub struct OutcomeBetPlaced {
+    pub user: Pubkey,
+    pub market: Pubkey,
+    pub outcome_index: u8,
+    pub amount: u64,
+    pub outcome_pool: u64,
+}
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[event]
+pub struct PositionReduced {
+    pub user: Pubkey,
+    pub market: Pubkey,
+    pub side: BetSide,
+    pub amount: u64,
+    pub exit_fee: u64,
+    /// Stake left on `side`
+    pub remaining: u64,
+}
 
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct MarketLocked {
+    pub market: Pubkey,
+    pub lock_time: i64,
+    pub total_pool: u64,
 }
 
-#[derive(Accounts)]
-#[instruction(oracle_feed: Pubkey, description: String, category: String)]
-pub struct CreateMarket<'info> {
-    #[account(
-        mut,
-        seeds = [b"arena_state"],
-        bump = arena_state.bump,
-        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
-    )]
-    pub arena_state: Account<'info, ArenaState>,
+#[event]
+pub struct SampleRecorded {
+    pub market: Pubkey,
+    pub timestamp: i64,
+    pub value: i128,
+    pub sample_count: u16,
+}
 
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + Market::INIT_SPACE,
-        seeds = [b"market", arena_state.total_markets.to_le_bytes().as_ref()],
-        bump,
-    )]
-    pub market: Account<'info, Market>,
+#[event]
+pub struct MarketResolved {
+    pub market: Pubkey,
+    pub start_value: i128,
+    pub end_value: i128,
+    pub outcome: MarketOutcome,
+    pub total_pool: u64,
+    pub fee_collected: u64,
+    pub dispute_deadline: i64,
+}
 
-    /// CHECK: Switchboard aggregator account
-    #[account(
-        constraint = oracle_feed.key() == oracle_feed @ ArenaError::InvalidOracle,
-    )]
-    pub oracle_feed: AccountLoader<'info, AggregatorAccountData>,
+#[event]
+pub struct MarketChallenged {
+    pub market: Pubkey,
+    pub challenger: Pubkey,
+    pub proposed_outcome: MarketOutcome,
+    pub bond: u64,
+}
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+#[event]
+pub struct DisputeSettled {
+    pub market: Pubkey,
+    pub challenger: Pubkey,
+    pub outcome: MarketOutcome,
+    /// True if the original outcome stood and the bond was slashed
+    pub upheld: bool,
+    pub bond: u64,
+    pub fee_collected: u64,
+}
 
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct MarketFinalized {
+    pub market: Pubkey,
+    pub outcome: MarketOutcome,
+    pub fee_collected: u64,
 }
 
-#[derive(Accounts)]
-pub struct PlaceBet<'info> {
-    #[account(
-        mut,
-        seeds = [b"arena_state"],
-        bump = arena_state.bump,
-    )]
-    pub arena_state: Account<'info, ArenaState>,
+#[event]
+pub struct WinningsClaimed {
+    pub user: Pubkey,
+    pub market: Pubkey,
+    pub payout: u64,
+}
 
-    #[account(
-        mut,
-        seeds = [b"market", market.id.to_le_bytes().as_ref()],
-        bump = market.bump,
-    )]
-    pub market: Account<'info, Market>,
+#[event]
+pub struct BetLost {
+    pub user: Pubkey,
+    pub market: Pubkey,
+    pub amount_lost: u64,
+}
 
-    #[account(
-        mut,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump = user_account.bump,
-        constraint = user_account.user == user.key(),
-    )]
-    pub user_account: Account<'info, UserAccount>,
+#[event]
+pub struct BetRefunded {
+    pub user: Pubkey,
+    pub market: Pubkey,
+    pub amount: u64,
+}
 
-    #[account(
-        init,
-        payer = user,
-        space = 8 + Position::INIT_SPACE,
-        seeds = [b"position", market.key().as_ref(), user.key().as_ref()],
-        bump,
-    )]
-    pub position: Account<'info, Position>,
+#[event]
+pub struct PositionSwept {
+    pub user: Pubkey,
+    pub market: Pubkey,
+    pub swept_by: Pubkey,
+}
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[event]
+pub struct MarketClosed {
+    pub market: Pubkey,
+    pub market_id: u64,
+    pub rent_receiver: Pubkey,
+}
 
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct FeesSwept {
+    pub treasury: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
 }
 
-#[derive(Accounts)]
-pub struct ResolveMarket<'info> {
-    #[account(
-        seeds = [b"arena_state"],
-        bump = arena_state.bump,
-    )]
-    pub arena_state: Account<'info, ArenaState>,
+#[event]
+pub struct CollateralAdded {
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+}
 
-    #[account(
-        mut,
-        seeds = [b"market", market.id.to_le_bytes().as_ref()],
-        bump = market.bump,
-    )]
-    pub market: Account<'info, Market>,
+#[event]
+pub struct CollateralUpdated {
+    pub mint: Pubkey,
+    pub enabled: bool,
+}
 
-    /// CHECK: Switchboard aggregator account
-    #[account(
-        constraint = oracle_feed.key() == market.oracle_feed @ ArenaError::InvalidOracle,
-    )]
-    pub oracle_feed: AccountLoader<'info, AggregatorAccountData>,
+#[event]
+pub struct ReferralFeeUpdated {
+    pub old_fee_bps: u16,
+    pub new_fee_bps: u16,
+}
 
-    pub resolver: Signer<'info>,
+#[event]
+pub struct FeedApproved {
+    pub feed: Pubkey,
+    pub oracle_kind: OracleKind,
+    pub oracle_queue: Pubkey,
+    pub min_oracle_results: u32,
+    pub require_locked: bool,
 }
 
-#[derive(Accounts)]
-pub struct ClaimWinnings<'info> {
-    #[account(
-        seeds = [b"arena_state"],
-        bump = arena_state.bump,
-    )]
-    pub arena_state: Account<'info, ArenaState>,
+#[event]
+pub struct FeedUpdated {
+    pub feed: Pubkey,
+    pub oracle_kind: OracleKind,
+    pub oracle_queue: Pubkey,
+    pub min_oracle_results: u32,
+    pub require_locked: bool,
+}
 
-    #[account(
-        mut,
-        seeds = [b"vault"],
-        bump,
-    )]
-    pub vault: Account<'info, TokenAccount>,
+#[event]
+pub struct FeedRemoved {
+    pub feed: Pubkey,
+}
 
-    #[account(
-        mut,
-        constraint = treasury.key() == arena_state.treasury @ ArenaError::InvalidTreasury,
-    )]
-    pub treasury: Account<'info, TokenAccount>,
+#[event]
+pub struct ArenaPaused {
+    /// Scopes paused by this call
+    pub scopes: u8,
+    /// All scopes paused afterwards
+    pub paused: u8,
+}
 
-    #[account(
-        seeds = [b"market", market.id.to_le_bytes().as_ref()],
-        bump = market.bump,
-    )]
-    pub market: Account<'info, Market>,
+#[event]
+pub struct ArenaUnpaused {
+    /// Scopes resumed by this call
+    pub scopes: u8,
+    /// Scopes still paused afterwards
+    pub paused: u8,
+}
 
-    #[account(
-        mut,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump = user_account.bump,
-        constraint = user_account.user == user.key(),
-    )]
-    pub user_account: Account<'info, UserAccount>,
+#[event]
+pub struct AdminChangeQueued {
+    pub change_id: u64,
+    pub proposer: Pubkey,
+    pub change: AdminChange,
+    pub executable_at: i64,
+}
 
-    #[account(
-        mut,
-        seeds = [b"position", market.key().as_ref(), user.key().as_ref()],
-        bump = position.bump,
-        constraint = position.user == user.key() @ ArenaError::Unauthorized,
-        constraint = position.market == market.key() @ ArenaError::InvalidPosition,
-    )]
-    pub position: Account<'info, Position>,
+#[event]
+pub struct AdminChangeExecuted {
+    pub change_id: u64,
+    pub change: AdminChange,
+}
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+#[event]
+pub struct AdminChangeCancelled {
+    pub change_id: u64,
+    pub change: AdminChange,
+}
+
+#[event]
+pub struct FeeUpdated {
+    pub old_fee_bps: u16,
+    pub new_fee_bps: u16,
+}
 
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct ExitFeeUpdated {
+    pub old_exit_fee_bps: u16,
+    pub new_exit_fee_bps: u16,
 }
 
-#[derive(Accounts)]
-pub struct UpdateFee<'info> {
-    #[account(
-        mut,
-        seeds = [b"arena_state"],
-        bump = arena_state.bump,
-        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
-    )]
-    pub arena_state: Account<'info, ArenaState>,
+#[event]
+pub struct OraclePolicyUpdated {
+    pub oracle_policy: OraclePolicy,
+}
 
-    pub authority: Signer<'info>,
+#[event]
+pub struct BetLimitsUpdated {
+    pub bet_limits: BetLimits,
+    pub max_open_exposure: u64,
 }
 
-#[derive(Accounts)]
-pub struct TransferAuthority<'info> {
-    #[account(
-        mut,
-        seeds = [b"arena_state"],
-        bump = arena_state.bump,
-        constraint = arena_state.authority == authority.key() @ ArenaError::Unauthorized,
-    )]
-    pub arena_state: Account<'info, ArenaState>,
+#[event]
+pub struct TimelockDelayUpdated {
+    pub old_delay_secs: i64,
+    pub new_delay_secs: i64,
+}
 
-    pub authority: Signer<'info>,
+#[event]
+pub struct MarketCancelled {
+    pub market: Pubkey,
+    pub cancelled_by: Pubkey,
+    pub reason: CancelReason,
+    pub total_pool: u64,
 }
 
-// ============================================================================
-// STATE
-// ============================================================================
+#[event]
+pub struct ClaimExpiryUpdated {
+    pub claim_expiry_secs: i64,
+}
 
-#[account]
-#[derive(InitSpace)]
-pub struct ArenaState {
-    pub authority: Pubkey,
-    pub treasury: Pubkey,
-    pub swtch_mint: Pubkey,
-    pub total_volume: u64,
-    pub total_markets: u64,
-    pub protocol_fee_bps: u16,
-    pub bump: u8,
+#[event]
+pub struct CreationConfigUpdated {
+    pub open_creation: bool,
+    pub creation_bond: u64,
+    pub creator_fee_bps: u16,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct Market {
-    pub id: u64,
-    pub oracle_feed: Pubkey,
-    #[max_len(200)]
-    pub description: String,
-    #[max_len(50)]
-    pub category: String,
-    pub start_value: i128,
-    pub resolution_time: i64,
-    pub total_up_pool: u64,
-    pub total_down_pool: u64,
-    pub resolved: bool,
-    pub outcome: Option<bool>,
-    pub bump: u8,
+#[event]
+pub struct CreationBondSettled {
+    pub market: Pubkey,
+    pub creator: Pubkey,
+    pub bond: u64,
+    /// Refunded on a finalized market, slashed on a cancelled one
+    pub refunded: bool,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct Position {
-    pub user: Pubkey,
+#[event]
+pub struct CreatorFeeClaimed {
     pub market: Pubkey,
-    pub prediction: bool,
+    pub creator: Pubkey,
     pub amount: u64,
-    pub claimed: bool,
-    pub bump: u8,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct UserAccount {
-    pub user: Pubkey,
-    pub balance: u64,
-    pub total_wagered: u64,
-    pub total_won: u64,
-    pub wins: u32,
-    pub losses: u32,
-    pub current_streak: u32,
-    pub best_streak: u32,
-    pub bump: u8,
+#[event]
+pub struct RoleGranted {
+    pub member: Pubkey,
+    /// Roles added by this call
+    pub roles: u8,
+    /// All roles the member holds afterwards
+    pub member_roles: u8,
 }
 
-// ============================================================================
-// EVENTS
-// ============================================================================
+#[event]
+pub struct RoleRevoked {
+    pub member: Pubkey,
+    /// Roles removed by this call
+    pub roles: u8,
+    /// Roles the member still holds afterwards
+    pub member_roles: u8,
+}
 
 #[event]
-pub struct ArenaInitialized {
+pub struct DisputeConfigUpdated {
+    pub dispute_period_secs: i64,
+    pub dispute_bond: u64,
+}
+
+#[event]
+pub struct AuthorityTransferProposed {
     pub authority: Pubkey,
-    pub treasury: Pubkey,
-    pub protocol_fee_bps: u16,
+    pub pending_authority: Pubkey,
 }
 
 #[event]
-pub struct Deposited {
-    pub user: Pubkey,
-    pub amount: u64,
-    pub new_balance: u64,
+pub struct AuthorityTransferred {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
 }
 
 #[event]
-pub struct Withdrawn {
-    pub user: Pubkey,
-    pub amount: u64,
-    pub new_balance: u64,
+pub struct AuthorityTransferCancelled {
+    pub cancelled: Pubkey,
 }
 
 #[event]
-pub struct MarketCreated {
-    pub market_id: u64,
-    pub oracle_feed: Pubkey,
-    pub description: String,
-    pub category: String,
-    pub start_value: i128,
-    pub resolution_time: i64,
+pub struct TreasuryTransferProposed {
+    pub treasury: Pubkey,
+    pub pending_treasury: Pubkey,
 }
 
 #[event]
-pub struct BetPlaced {
-    pub user: Pubkey,
-    pub market: Pubkey,
-    pub prediction: bool,
-    pub amount: u64,
-    pub total_up_pool: u64,
-    pub total_down_pool: u64,
+pub struct TreasuryTransferred {
+    pub old_treasury: Pubkey,
+    pub new_treasury: Pubkey,
 }
 
 #[event]
-pub struct MarketResolved {
-    pub market: Pubkey,
-    pub start_value: i128,
-    pub end_value: i128,
-    pub outcome: bool,
-    pub total_pool: u64,
+pub struct TreasuryTransferCancelled {
+    pub cancelled: Pubkey,
 }
 
 #[event]
-pub struct WinningsClaimed {
-    pub user: Pubkey,
-    pub market: Pubkey,
-    pub payout: u64,
-    pub fee: u64,
+pub struct CouncilUpdated {
+    pub members: Vec<Pubkey>,
+    pub threshold: u8,
 }
 
 #[event]
-pub struct BetLost {
-    pub user: Pubkey,
-    pub market: Pubkey,
-    pub amount_lost: u64,
+pub struct CouncilActionProposed {
+    pub action_id: u64,
+    pub proposer: Pubkey,
 }
 
 #[event]
-pub struct FeeUpdated {
-    pub old_fee_bps: u16,
-    pub new_fee_bps: u16,
+pub struct CouncilActionApproved {
+    pub action_id: u64,
+    pub member: Pubkey,
+    /// Approvals from current members so far
+    pub approvals: u8,
 }
 
 #[event]
-pub struct AuthorityTransferred {
-    pub old_authority: Pubkey,
-    pub new_authority: Pubkey,
+pub struct CouncilActionExecuted {
+    pub action_id: u64,
+}
+
+#[event]
+pub struct CouncilActionCancelled {
+    pub action_id: u64,
 }
 
 // ============================================================================
@@ -893,10 +4857,10 @@ pub enum ArenaError {
     #[msg("Resolution time not reached")]
     ResolutionTimeNotReached,
 
-    #[msg("Bet amount too small (minimum 1 $SWTCH)")]
+    #[msg("Bet amount is below the market minimum")]
     BetTooSmall,
 
-    #[msg("Bet amount too large (maximum 10,000 $SWTCH)")]
+    #[msg("Bet amount is above the market maximum")]
     BetTooLarge,
 
     #[msg("Already claimed winnings")]
@@ -910,4 +4874,499 @@ pub enum ArenaError {
 
     #[msg("Invalid position")]
     InvalidPosition,
+
+    #[msg("Instruction not supported for this market type")]
+    InvalidMarketType,
+
+    #[msg("Invalid number of outcomes (2 to 8)")]
+    InvalidOutcomeCount,
+
+    #[msg("Outcome label empty or too long (max 32 characters)")]
+    InvalidOutcomeLabel,
+
+    #[msg("Outcome ranges must be ascending with one boundary per adjacent pair")]
+    InvalidOutcomeRanges,
+
+    #[msg("Invalid outcome index")]
+    InvalidOutcomeIndex,
+
+    #[msg("Scalar lower bound must be below upper bound")]
+    InvalidScalarBounds,
+
+    #[msg("No fees to sweep")]
+    NoFeesToSweep,
+
+    #[msg("Invalid oracle policy")]
+    InvalidOraclePolicy,
+
+    #[msg("Oracle value is stale")]
+    StaleOracle,
+
+    #[msg("Too few oracles responded")]
+    InsufficientOracleResults,
+
+    #[msg("Oracle responses deviate too much")]
+    OracleDeviationTooHigh,
+
+    #[msg("Invalid settlement window configuration")]
+    InvalidSettlementConfig,
+
+    #[msg("Market settles on a single oracle read")]
+    SamplingNotEnabled,
+
+    #[msg("Outside the settlement window")]
+    OutsideSettlementWindow,
+
+    #[msg("Oracle has not updated since the last sample")]
+    DuplicateSample,
+
+    #[msg("Settlement sample buffer is full")]
+    SampleBufferFull,
+
+    #[msg("Too few settlement samples collected")]
+    InsufficientSamples,
+
+    #[msg("Aggregator history buffer missing or does not match the feed")]
+    InvalidHistoryBuffer,
+
+    #[msg("No oracle round at or after resolution time yet")]
+    OracleRoundNotAvailable,
+
+    #[msg("Invalid dispute configuration")]
+    InvalidDisputeConfig,
+
+    #[msg("Outcome is not valid for this market")]
+    InvalidOutcome,
+
+    #[msg("Market cannot be challenged or finalized in its current state")]
+    MarketNotChallengeable,
+
+    #[msg("Dispute period has ended")]
+    DisputePeriodEnded,
+
+    #[msg("Dispute period still active")]
+    DisputePeriodActive,
+
+    #[msg("Market is disputed")]
+    MarketDisputed,
+
+    #[msg("Market is not disputed")]
+    MarketNotDisputed,
+
+    #[msg("Market has been cancelled")]
+    MarketCancelled,
+
+    #[msg("Market can no longer be cancelled")]
+    MarketNotCancellable,
+
+    #[msg("Challenger's user account is required to refund the bond")]
+    ChallengerAccountRequired,
+
+    #[msg("Position does not hold that much on this side")]
+    InsufficientStake,
+
+    #[msg("Lock time must be in the future and no later than resolution time")]
+    InvalidLockTime,
+
+    #[msg("Market is not open")]
+    MarketNotOpen,
+
+    #[msg("Lock time not reached")]
+    LockTimeNotReached,
+
+    #[msg("Market is not finalized or cancelled")]
+    MarketNotFinal,
+
+    #[msg("Market still has unsettled positions")]
+    PositionsStillOpen,
+
+    #[msg("Position cannot be swept before the claim expiry")]
+    ClaimNotExpired,
+
+    #[msg("Claim expiry must be positive")]
+    InvalidClaimExpiry,
+
+    #[msg("Collateral mint is not accepted")]
+    CollateralNotAllowed,
+
+    #[msg("Collateral account does not match the market")]
+    InvalidCollateral,
+
+    #[msg("Token-2022 mint uses an unsupported extension")]
+    UnsupportedMintExtension,
+
+    #[msg("This part of the arena is paused")]
+    Paused,
+
+    #[msg("Invalid pause scope")]
+    InvalidPauseScope,
+
+    #[msg("Invalid authority")]
+    InvalidAuthority,
+
+    #[msg("No transfer is pending")]
+    NoPendingTransfer,
+
+    #[msg("Invalid role")]
+    InvalidRole,
+
+    #[msg("Role registry is full")]
+    RoleRegistryFull,
+
+    #[msg("Key holds no roles")]
+    RoleNotHeld,
+
+    #[msg("Timelock delay has not elapsed")]
+    TimelockNotElapsed,
+
+    #[msg("Invalid timelock delay")]
+    InvalidTimelockDelay,
+
+    #[msg("Invalid bet limits")]
+    InvalidBetLimits,
+
+    #[msg("Position would exceed the market's per-user cap")]
+    PositionLimitExceeded,
+
+    #[msg("Open exposure would exceed the per-user cap")]
+    ExposureLimitExceeded,
+
+    #[msg("Creator's $SWTCH account is required for the creation bond")]
+    CreatorAccountRequired,
+
+    #[msg("Market has no creation bond to settle")]
+    NoCreationBond,
+
+    #[msg("No creator fee to claim")]
+    NoCreatorFee,
+
+    #[msg("Creator bond or fee has not been settled")]
+    CreatorFundsUnsettled,
+
+    #[msg("Invalid council members or threshold")]
+    InvalidCouncil,
+
+    #[msg("Signer is not a council member")]
+    NotCouncilMember,
+
+    #[msg("Already approved this action")]
+    AlreadyApproved,
+
+    #[msg("Not enough council approvals")]
+    CouncilThresholdNotMet,
+
+    #[msg("Council action is too large")]
+    InvalidCouncilAction,
+
+    #[msg("Oracle feed is not approved")]
+    FeedNotApproved,
+
+    #[msg("Invalid feed approval (min oracles must be positive, label max 32 characters)")]
+    InvalidFeedApproval,
+
+    #[msg("Oracle feed is not served by its approved queue")]
+    FeedQueueMismatch,
+
+    #[msg("Oracle feed configuration is not locked")]
+    FeedNotLocked,
+
+    #[msg("Invalid referrer (users cannot refer themselves)")]
+    InvalidReferrer,
+
+    #[msg("Referrer account required to settle a referred user's winnings")]
+    ReferrerAccountRequired,
+
+    #[msg("Samples account must be passed for settlement window markets only")]
+    InvalidSamplesAccount,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use switchboard_solana::SwitchboardDecimal;
+
+    fn market(market_type: MarketType, up: u64, down: u64, outcome_pools: Vec<u64>) -> Market {
+        let zeroed = vec![0u8; Market::INIT_SPACE];
+        let mut market = Market::deserialize(&mut zeroed.as_slice()).unwrap();
+        market.market_type = market_type;
+        market.total_up_pool = up;
+        market.total_down_pool = down;
+        market.outcome_pools = outcome_pools;
+        market
+    }
+
+    fn position(side: BetSide, amount: u64) -> Position {
+        let mut position = Position {
+            user: Pubkey::default(),
+            market: Pubkey::default(),
+            up_amount: 0,
+            down_amount: 0,
+            outcome_amounts: [0; MAX_OUTCOMES],
+            amount: 0,
+            claimed: false,
+            bump: 0,
+        };
+        position.add_stake(side, amount).unwrap();
+        position
+    }
+
+    /// Resolves `market` at `end_value` and checks that the escrowed fee plus
+    /// every claim never exceeds what was bet, leaving at most one unit of
+    /// rounding dust per position
+    fn assert_solvent(mut market: Market, positions: &[Position], end_value: i128) {
+        let outcome = market.outcome_for(end_value).unwrap();
+        let total_pool = market.total_pool().unwrap();
+        market.outcome = Some(outcome);
+        market.fee_collected = if outcome == MarketOutcome::Void {
+            0
+        } else {
+            protocol_fee(total_pool, DEFAULT_PROTOCOL_FEE_BPS).unwrap()
+        };
+
+        let paid: u64 = positions
+            .iter()
+            .map(|position| {
+                if outcome == MarketOutcome::Void {
+                    position.amount
+                } else {
+                    market.payout_for(position, total_pool - market.fee_collected).unwrap()
+                }
+            })
+            .sum();
+
+        let owed = paid + market.fee_collected;
+        assert!(owed <= total_pool, "vault insolvent: owes {owed} of {total_pool}");
+        assert!(total_pool - owed <= positions.len() as u64, "excess dust left in vault");
+    }
+
+    #[test]
+    fn up_down_market_is_solvent() {
+        let mut hedged = position(BetSide::Up, 500_000);
+        hedged.add_stake(BetSide::Down, 250_000).unwrap();
+        let positions = [
+            position(BetSide::Up, 333_333),
+            position(BetSide::Up, 1_000_001),
+            position(BetSide::Up, 7),
+            position(BetSide::Down, 2_500_000),
+            hedged,
+        ];
+        let mut market = market(MarketType::UpDown, 1_833_341, 2_750_000, vec![]);
+        market.start_value = 100;
+
+        assert_solvent(market.clone(), &positions, 101);
+        assert_solvent(market, &positions, 99);
+    }
+
+    #[test]
+    fn categorical_market_is_solvent() {
+        let positions = [
+            position(BetSide::Outcome { index: 0 }, 10_000_000),
+            position(BetSide::Outcome { index: 1 }, 3_333_333),
+            position(BetSide::Outcome { index: 1 }, 6_666_667),
+            position(BetSide::Outcome { index: 2 }, 1),
+        ];
+        let mut market = market(
+            MarketType::Categorical,
+            0,
+            0,
+            vec![10_000_000, 10_000_000, 1],
+        );
+        market.categorical_resolution = CategoricalResolution::Index;
+
+        for index in 0..3 {
+            assert_solvent(market.clone(), &positions, index);
+        }
+        assert!(market.outcome_for(3).unwrap() == MarketOutcome::Void);
+        assert!(market.outcome_for(-1).unwrap() == MarketOutcome::Void);
+    }
+
+    #[test]
+    fn scalar_market_is_solvent() {
+        let positions = [
+            position(BetSide::Up, 4_000_000),
+            position(BetSide::Up, 1_234_567),
+            position(BetSide::Down, 999_999),
+            position(BetSide::Down, 3),
+        ];
+        let mut market = market(MarketType::Scalar, 5_234_567, 1_000_002, vec![]);
+        market.lower_bound = -50;
+        market.upper_bound = 50;
+
+        for end_value in [-100, -49, 0, 17, 49, 100] {
+            assert_solvent(market.clone(), &positions, end_value);
+        }
+    }
+
+    #[test]
+    fn referral_rebates_stay_within_fee() {
+        let positions = [
+            position(BetSide::Up, 333_333),
+            position(BetSide::Up, 1_000_001),
+            position(BetSide::Down, 2_500_000),
+        ];
+        let mut market = market(MarketType::UpDown, 1_333_334, 2_500_000, vec![]);
+        market.outcome = Some(MarketOutcome::Up);
+        market.creator_fee_bps = MAX_CREATOR_FEE_BPS;
+        market.referral_fee_bps = MAX_REFERRAL_FEE_BPS;
+        let mut collateral = CollateralConfig {
+            mint: Pubkey::default(),
+            enabled: true,
+            total_volume: 0,
+            accrued_fees: 0,
+            bump: 0,
+        };
+
+        let fee = protocol_fee(market.total_pool().unwrap(), DEFAULT_PROTOCOL_FEE_BPS).unwrap();
+        escrow_fee(&mut market, &mut collateral, fee).unwrap();
+        assert_eq!(collateral.accrued_fees + market.creator_fee + market.referral_reserve, fee);
+
+        let rebates: u64 = positions
+            .iter()
+            .map(|position| market.payout_for(position, market.referral_reserve).unwrap())
+            .sum();
+        assert!(rebates <= market.referral_reserve);
+    }
+
+    #[test]
+    fn claims_wait_for_the_escrowed_fee() {
+        let mut positions = [
+            position(BetSide::Up, 333_333),
+            position(BetSide::Up, 1_000_001),
+            position(BetSide::Down, 2_500_000),
+        ];
+        let mut market = market(MarketType::UpDown, 1_333_334, 2_500_000, vec![]);
+        market.resolved = true;
+        market.status = MarketStatus::Resolved;
+        market.outcome = Some(MarketOutcome::Up);
+        market.creator_fee_bps = MAX_CREATOR_FEE_BPS;
+        market.referral_fee_bps = MAX_REFERRAL_FEE_BPS;
+        let total_pool = market.total_pool().unwrap();
+        market.fee_collected = protocol_fee(total_pool, DEFAULT_PROTOCOL_FEE_BPS).unwrap();
+        let mut collateral = CollateralConfig {
+            mint: Pubkey::default(),
+            enabled: true,
+            total_volume: 0,
+            accrued_fees: 0,
+            bump: 0,
+        };
+        let zeroed = vec![0u8; UserAccount::INIT_SPACE];
+        let mut user = UserAccount::deserialize(&mut zeroed.as_slice()).unwrap();
+        let mut referrer = UserAccount::deserialize(&mut zeroed.as_slice()).unwrap();
+
+        // Nothing can be paid before the fee is escrowed at finalization
+        assert!(market.check_claimable().is_err());
+        let fee = market.fee_collected;
+        escrow_fee(&mut market, &mut collateral, fee).unwrap();
+        market.status = MarketStatus::Finalized;
+        market.check_claimable().unwrap();
+
+        let referrers = [Pubkey::new_unique(), Pubkey::default(), Pubkey::default()];
+        for (position, referred_by) in positions.iter_mut().zip(referrers) {
+            let referrer_account = (referred_by != Pubkey::default()).then_some(&mut referrer);
+            settle_position(
+                &mut market,
+                position,
+                &mut user,
+                &mut collateral,
+                referred_by,
+                referrer_account,
+            )
+            .unwrap();
+        }
+        collateral.accrued_fees += market.referral_reserve - market.referral_paid;
+
+        let owed = user.balance + referrer.balance + collateral.accrued_fees + market.creator_fee;
+        assert!(owed <= total_pool, "vault insolvent: owes {owed} of {total_pool}");
+        assert!(total_pool - owed <= positions.len() as u64, "excess dust left in vault");
+        assert!(referrer.referral_earnings > 0);
+    }
+
+    #[test]
+    fn pull_feed_values_read_in_whole_units() {
+        assert_eq!(from_pull_feed(2 * PULL_FEED_SCALE), 2);
+        assert_eq!(from_pull_feed(PULL_FEED_SCALE * 3 / 2), 1);
+        assert_eq!(from_pull_feed(PULL_FEED_SCALE * 199 / 100), 1);
+        assert_eq!(from_pull_feed(-PULL_FEED_SCALE * 5 / 2), -2);
+        assert_eq!(from_pull_feed(PULL_FEED_SCALE / 3), 0);
+
+        // Same reading as a V2 result of 2.5 and -2.5
+        let v2 = |mantissa: i128| -> i128 {
+            SwitchboardDecimal { mantissa, scale: 1 }.try_into().unwrap()
+        };
+        assert_eq!(from_pull_feed(PULL_FEED_SCALE * 5 / 2), v2(25));
+        assert_eq!(from_pull_feed(-PULL_FEED_SCALE * 5 / 2), v2(-25));
+
+        // An outcome index published by a pull feed selects that outcome
+        let mut market = market(MarketType::Categorical, 0, 0, vec![1, 1, 1]);
+        market.categorical_resolution = CategoricalResolution::Index;
+        let index = from_pull_feed(2 * PULL_FEED_SCALE);
+        assert!(market.outcome_for(index).unwrap() == MarketOutcome::Outcome { index: 2 });
+    }
+
+    #[test]
+    fn spot_round_comes_from_unwrapped_history() {
+        let row = |timestamp: i64, mantissa: i128| AggregatorHistoryRow {
+            timestamp,
+            value: SwitchboardDecimal { mantissa, scale: 0 },
+        };
+        let round = |rows: &[AggregatorHistoryRow]| {
+            first_round_as_of(rows, 1_000, 300).map(|row| row.timestamp)
+        };
+
+        // Ring order does not matter, only timestamps
+        assert_eq!(round(&[row(1_100, 7), row(990, 6), row(1_020, 5)]), Some(1_020));
+        // Nothing yet, or only a round too long after resolution
+        assert_eq!(round(&[row(990, 6), row(0, 0)]), None);
+        assert_eq!(round(&[row(990, 6), row(1_301, 7)]), None);
+        // Wrapped past resolution time: the first round may be gone
+        assert_eq!(round(&[row(1_100, 7), row(1_050, 6), row(1_020, 5)]), None);
+    }
+
+    #[test]
+    fn settlement_samples_average_over_time() {
+        let samples = MarketSamples {
+            market: Pubkey::default(),
+            samples: vec![
+                OracleSample { timestamp: 0, value: 100 },
+                OracleSample { timestamp: 10, value: 400 },
+                OracleSample { timestamp: 40, value: 1_000 },
+            ],
+            bump: 0,
+        };
+
+        // 100 for 10s, 400 for 30s, 1000 for 20s
+        assert_eq!(samples.time_weighted_average(60).unwrap(), 550);
+        assert_eq!(samples.median(), 400);
+    }
+
+    #[test]
+    fn one_sided_market_refunds_in_full() {
+        let positions = [position(BetSide::Down, 5_000_000), position(BetSide::Down, 1)];
+        let mut market = market(MarketType::UpDown, 0, 5_000_001, vec![]);
+        market.start_value = 100;
+
+        assert!(market.outcome_for(101).unwrap() == MarketOutcome::Void);
+        assert_solvent(market, &positions, 101);
+    }
+
+    #[test]
+    fn exposure_is_released_after_resolution() {
+        let zeroed = vec![0u8; UserAccount::INIT_SPACE];
+        let mut user = UserAccount::deserialize(&mut zeroed.as_slice()).unwrap();
+
+        add_exposure(&mut user, 600, 1_000, 0, 1_000).unwrap();
+        assert!(add_exposure(&mut user, 600, 2_000, 500, 1_000).is_err());
+
+        // The first market has resolved but its win is still unclaimed
+        let mut user = UserAccount::deserialize(&mut zeroed.as_slice()).unwrap();
+        add_exposure(&mut user, 600, 1_000, 0, 1_000).unwrap();
+        add_exposure(&mut user, 600, 2_000, 1_000, 1_000).unwrap();
+        assert_eq!(user.open_exposure, 600);
+
+        // Claiming the old stake leaves the new one counted
+        release_exposure(&mut user, 1_000, 600).unwrap();
+        assert_eq!(user.open_exposure, 600);
+        release_exposure(&mut user, 2_000, 600).unwrap();
+        assert_eq!(user.open_exposure, 0);
+    }
 }
@@ -9,6 +9,7 @@ import {
 } from "@solana/web3.js";
 import {
   TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
   createMint,
   createAccount,
   mintTo,
@@ -33,9 +34,14 @@ describe("feedgod-arena", () => {
   
   // PDAs
   let arenaStatePda: PublicKey;
+  let roleRegistryPda: PublicKey;
+  let swtchCollateralPda: PublicKey;
+  let approvedFeedPda: PublicKey;
   let vaultPda: PublicKey;
   let user1AccountPda: PublicKey;
   let user2AccountPda: PublicKey;
+  let user1ReferralPda: PublicKey;
+  let user2ReferralPda: PublicKey;
   let marketPda: PublicKey;
   let positionPda: PublicKey;
 
@@ -126,18 +132,38 @@ describe("feedgod-arena", () => {
       program.programId
     );
 
+    [roleRegistryPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("roles")],
+      program.programId
+    );
+
+    [swtchCollateralPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("collateral"), swtchMint.toBuffer()],
+      program.programId
+    );
+
     [vaultPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("vault")],
+      [Buffer.from("vault"), swtchMint.toBuffer()],
       program.programId
     );
 
     [user1AccountPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("user_account"), user1.publicKey.toBuffer()],
+      [Buffer.from("user_account"), user1.publicKey.toBuffer(), swtchMint.toBuffer()],
       program.programId
     );
 
     [user2AccountPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("user_account"), user2.publicKey.toBuffer()],
+      [Buffer.from("user_account"), user2.publicKey.toBuffer(), swtchMint.toBuffer()],
+      program.programId
+    );
+
+    [user1ReferralPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("referral"), user1.publicKey.toBuffer()],
+      program.programId
+    );
+
+    [user2ReferralPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("referral"), user2.publicKey.toBuffer()],
       program.programId
     );
   });
@@ -148,9 +174,11 @@ describe("feedgod-arena", () => {
         .initialize(PROTOCOL_FEE_BPS)
         .accounts({
           arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          swtchCollateral: swtchCollateralPda,
           vault: vaultPda,
           swtchMint: swtchMint,
-          treasury: treasuryTokenAccount,
+          treasury: treasury.publicKey,
           authority: provider.wallet.publicKey,
           tokenProgram: TOKEN_PROGRAM_ID,
           systemProgram: SystemProgram.programId,
@@ -164,8 +192,13 @@ describe("feedgod-arena", () => {
         provider.wallet.publicKey.toString()
       );
       expect(arenaState.protocolFeeBps).to.equal(PROTOCOL_FEE_BPS);
-      expect(arenaState.totalVolume.toNumber()).to.equal(0);
       expect(arenaState.totalMarkets.toNumber()).to.equal(0);
+      expect(arenaState.disputePeriodSecs.toNumber()).to.equal(86_400);
+
+      const collateral = await program.account.collateralConfig.fetch(swtchCollateralPda);
+      expect(collateral.mint.toString()).to.equal(swtchMint.toString());
+      expect(collateral.enabled).to.equal(true);
+      expect(collateral.totalVolume.toNumber()).to.equal(0);
     });
 
     it("should reject invalid fee percentage", async () => {
@@ -174,9 +207,11 @@ describe("feedgod-arena", () => {
           .initialize(1500) // 15% - too high
           .accounts({
             arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            swtchCollateral: swtchCollateralPda,
             vault: vaultPda,
             swtchMint: swtchMint,
-            treasury: treasuryTokenAccount,
+            treasury: treasury.publicKey,
             authority: provider.wallet.publicKey,
             tokenProgram: TOKEN_PROGRAM_ID,
             systemProgram: SystemProgram.programId,
@@ -202,9 +237,13 @@ describe("feedgod-arena", () => {
         .deposit(new anchor.BN(DEPOSIT_AMOUNT))
         .accounts({
           arenaState: arenaStatePda,
+          collateral: swtchCollateralPda,
+          mint: swtchMint,
           vault: vaultPda,
           userAccount: user1AccountPda,
           userTokenAccount: user1TokenAccount,
+          referral: user1ReferralPda,
+          referrerReferral: null,
           user: user1.publicKey,
           tokenProgram: TOKEN_PROGRAM_ID,
           systemProgram: SystemProgram.programId,
@@ -225,15 +264,71 @@ describe("feedgod-arena", () => {
       );
     });
 
+    it("should record a referrer on the first deposit", async () => {
+      await program.methods
+        .deposit(new anchor.BN(DEPOSIT_AMOUNT))
+        .accounts({
+          arenaState: arenaStatePda,
+          collateral: swtchCollateralPda,
+          mint: swtchMint,
+          vault: vaultPda,
+          userAccount: user2AccountPda,
+          userTokenAccount: user2TokenAccount,
+          referral: user2ReferralPda,
+          referrerReferral: user1ReferralPda,
+          user: user2.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user2])
+        .rpc();
+
+      const referral = await program.account.referral.fetch(user2ReferralPda);
+      expect(referral.referrer.toString()).to.equal(user1.publicKey.toString());
+      expect(referral.deposited).to.equal(true);
+
+      const referrerReferral = await program.account.referral.fetch(user1ReferralPda);
+      expect(referrerReferral.referralCount).to.equal(1);
+    });
+
+    it("should reject self-referral", async () => {
+      try {
+        await program.methods
+          .deposit(new anchor.BN(1_000_000))
+          .accounts({
+            arenaState: arenaStatePda,
+            collateral: swtchCollateralPda,
+            mint: swtchMint,
+            vault: vaultPda,
+            userAccount: user1AccountPda,
+            userTokenAccount: user1TokenAccount,
+            referral: user1ReferralPda,
+            referrerReferral: user1ReferralPda,
+            user: user1.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([user1])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidReferrer");
+      }
+    });
+
     it("should reject zero deposit", async () => {
       try {
         await program.methods
           .deposit(new anchor.BN(0))
           .accounts({
             arenaState: arenaStatePda,
+            collateral: swtchCollateralPda,
+            mint: swtchMint,
             vault: vaultPda,
             userAccount: user1AccountPda,
             userTokenAccount: user1TokenAccount,
+            referral: user1ReferralPda,
+            referrerReferral: null,
             user: user1.publicKey,
             tokenProgram: TOKEN_PROGRAM_ID,
             systemProgram: SystemProgram.programId,
@@ -258,6 +353,7 @@ describe("feedgod-arena", () => {
         .withdraw(new anchor.BN(withdrawAmount))
         .accounts({
           arenaState: arenaStatePda,
+          mint: swtchMint,
           vault: vaultPda,
           userAccount: user1AccountPda,
           userTokenAccount: user1TokenAccount,
@@ -279,6 +375,7 @@ describe("feedgod-arena", () => {
           .withdraw(new anchor.BN(999_999_999_999)) // Way more than balance
           .accounts({
             arenaState: arenaStatePda,
+            mint: swtchMint,
             vault: vaultPda,
             userAccount: user1AccountPda,
             userTokenAccount: user1TokenAccount,
@@ -299,6 +396,37 @@ describe("feedgod-arena", () => {
   // In production, use @switchboard-xyz/on-demand testing utilities
   
   describe("create_market (requires mock oracle)", () => {
+    it("should approve the oracle feed", async () => {
+      [approvedFeedPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("approved_feed"), mockOracleFeed.publicKey.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .approveFeed({
+          oracleKind: { aggregatorV2: {} },
+          oracleQueue: Keypair.generate().publicKey,
+          minOracleResults: 1,
+          label: "BTC/USD",
+          category: "crypto",
+          requireLocked: false,
+        })
+        .accounts({
+          arenaState: arenaStatePda,
+          approvedFeed: approvedFeedPda,
+          oracleFeed: mockOracleFeed.publicKey,
+          authority: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const approvedFeed = await program.account.approvedFeed.fetch(approvedFeedPda);
+      expect(approvedFeed.feed.toString()).to.equal(mockOracleFeed.publicKey.toString());
+      expect(approvedFeed.label).to.equal("BTC/USD");
+      expect(approvedFeed.requireLocked).to.equal(false);
+    });
+
     it.skip("should create a market", async () => {
       // This test requires setting up a mock Switchboard PullFeed
       // Use @switchboard-xyz/on-demand for proper testing
@@ -311,17 +439,29 @@ describe("feedgod-arena", () => {
       );
 
       await program.methods
-        .createMarket(
-          mockOracleFeed.publicKey,
-          "Will BTC go up?",
-          "crypto",
-          new anchor.BN(resolutionTime)
-        )
+        .createMarket({
+          oracleFeed: mockOracleFeed.publicKey,
+          description: "Will BTC go up?",
+          category: "crypto",
+          lockTime: new anchor.BN(resolutionTime - 600),
+          resolutionTime: new anchor.BN(resolutionTime),
+          kind: { upDown: {} },
+          voidOnTie: false,
+          oracleKind: { aggregatorV2: {} },
+          oraclePolicy: null,
+          betLimits: null,
+          settlement: { mode: { spot: {} }, windowSecs: new anchor.BN(0), minSamples: 0 },
+        })
         .accounts({
           arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
           market: marketPda,
+          samples: null,
+          collateral: swtchCollateralPda,
           oracleFeed: mockOracleFeed.publicKey,
-          authority: provider.wallet.publicKey,
+          approvedFeed: approvedFeedPda,
+          creatorAccount: null,
+          creator: provider.wallet.publicKey,
           systemProgram: SystemProgram.programId,
         })
         .rpc();
@@ -330,6 +470,159 @@ describe("feedgod-arena", () => {
       expect(market.description).to.equal("Will BTC go up?");
       expect(market.category).to.equal("crypto");
       expect(market.resolved).to.equal(false);
+      expect(market.marketType).to.deep.equal({ upDown: {} });
+      expect(market.oracleKind).to.deep.equal({ aggregatorV2: {} });
+    });
+
+    it.skip("should create a threshold market", async () => {
+      const resolutionTime = Math.floor(Date.now() / 1000) + 3600;
+      const strikeValue = new anchor.BN(100_000);
+
+      const [thresholdMarketPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("market"), Buffer.from([1, 0, 0, 0, 0, 0, 0, 0])], // market id 1
+        program.programId
+      );
+
+      await program.methods
+        .createMarket({
+          oracleFeed: mockOracleFeed.publicKey,
+          description: "Will BTC be above 100k?",
+          category: "crypto",
+          lockTime: new anchor.BN(resolutionTime - 600),
+          resolutionTime: new anchor.BN(resolutionTime),
+          kind: { threshold: { strikeValue, direction: { above: {} } } },
+          voidOnTie: true,
+          oracleKind: { aggregatorV2: {} },
+          oraclePolicy: null,
+          betLimits: null,
+          settlement: { mode: { spot: {} }, windowSecs: new anchor.BN(0), minSamples: 0 },
+        })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          market: thresholdMarketPda,
+          samples: null,
+          collateral: swtchCollateralPda,
+          oracleFeed: mockOracleFeed.publicKey,
+          approvedFeed: approvedFeedPda,
+          creatorAccount: null,
+          creator: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const market = await program.account.market.fetch(thresholdMarketPda);
+      expect(market.marketType).to.deep.equal({ threshold: {} });
+      expect(market.strikeValue.toString()).to.equal(strikeValue.toString());
+      expect(market.thresholdDirection).to.deep.equal({ above: {} });
+      expect(market.voidOnTie).to.equal(true);
+    });
+
+    it.skip("should create a categorical market", async () => {
+      const resolutionTime = Math.floor(Date.now() / 1000) + 3600;
+
+      const [categoricalMarketPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("market"), Buffer.from([2, 0, 0, 0, 0, 0, 0, 0])], // market id 2
+        program.programId
+      );
+
+      await program.methods
+        .createMarket({
+          oracleFeed: mockOracleFeed.publicKey,
+          description: "Where will ETH close?",
+          category: "crypto",
+          lockTime: new anchor.BN(resolutionTime - 600),
+          resolutionTime: new anchor.BN(resolutionTime),
+          kind: {
+            categorical: {
+              labels: ["< 3k", "3k - 4k", ">= 4k"],
+              resolution: { ranges: {} },
+              rangeBounds: [new anchor.BN(3_000), new anchor.BN(4_000)],
+            },
+          },
+          voidOnTie: false,
+          oracleKind: { aggregatorV2: {} },
+          oraclePolicy: null,
+          betLimits: null,
+          settlement: { mode: { spot: {} }, windowSecs: new anchor.BN(0), minSamples: 0 },
+        })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          market: categoricalMarketPda,
+          samples: null,
+          collateral: swtchCollateralPda,
+          oracleFeed: mockOracleFeed.publicKey,
+          approvedFeed: approvedFeedPda,
+          creatorAccount: null,
+          creator: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const market = await program.account.market.fetch(categoricalMarketPda);
+      expect(market.marketType).to.deep.equal({ categorical: {} });
+      expect(market.outcomeLabels).to.deep.equal(["< 3k", "3k - 4k", ">= 4k"]);
+      expect(market.outcomePools.map((p) => p.toNumber())).to.deep.equal([0, 0, 0]);
+    });
+
+    it.skip("should create a scalar market", async () => {
+      const resolutionTime = Math.floor(Date.now() / 1000) + 3600;
+
+      const [scalarMarketPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("market"), Buffer.from([3, 0, 0, 0, 0, 0, 0, 0])], // market id 3
+        program.programId
+      );
+
+      await program.methods
+        .createMarket({
+          oracleFeed: mockOracleFeed.publicKey,
+          description: "NYC high temperature tomorrow",
+          category: "weather",
+          lockTime: new anchor.BN(resolutionTime - 600),
+          resolutionTime: new anchor.BN(resolutionTime),
+          kind: {
+            scalar: { lowerBound: new anchor.BN(0), upperBound: new anchor.BN(40) },
+          },
+          voidOnTie: false,
+          oracleKind: { aggregatorV2: {} },
+          oraclePolicy: null,
+          betLimits: null,
+          settlement: { mode: { spot: {} }, windowSecs: new anchor.BN(0), minSamples: 0 },
+        })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          market: scalarMarketPda,
+          samples: null,
+          collateral: swtchCollateralPda,
+          oracleFeed: mockOracleFeed.publicKey,
+          approvedFeed: approvedFeedPda,
+          creatorAccount: null,
+          creator: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const market = await program.account.market.fetch(scalarMarketPda);
+      expect(market.marketType).to.deep.equal({ scalar: {} });
+      expect(market.lowerBound.toNumber()).to.equal(0);
+      expect(market.upperBound.toNumber()).to.equal(40);
+    });
+  });
+
+  describe("lock_market (requires active market)", () => {
+    it.skip("should reject locking before lock time", async () => {
+      try {
+        await program.methods
+          .lockMarket()
+          .accounts({ market: marketPda })
+          .rpc();
+
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.message).to.include("LockTimeNotReached");
+      }
     });
   });
 
@@ -351,6 +644,7 @@ describe("feedgod-arena", () => {
         .accounts({
           arenaState: arenaStatePda,
           market: marketPda,
+          collateral: swtchCollateralPda,
           userAccount: user1AccountPda,
           position: positionPda,
           user: user1.publicKey,
@@ -360,70 +654,1251 @@ describe("feedgod-arena", () => {
         .rpc();
 
       const position = await program.account.position.fetch(positionPda);
-      expect(position.prediction).to.equal(true);
+      expect(position.upAmount.toNumber()).to.equal(BET_AMOUNT);
+      expect(position.downAmount.toNumber()).to.equal(0);
       expect(position.amount.toNumber()).to.equal(BET_AMOUNT);
       expect(position.claimed).to.equal(false);
     });
+
+    it.skip("should track open exposure and reject bets under the minimum", async () => {
+      const userAccount = await program.account.userAccount.fetch(user1AccountPda);
+      expect(userAccount.openExposure.toNumber()).to.equal(BET_AMOUNT);
+
+      const market = await program.account.market.fetch(marketPda);
+      try {
+        await program.methods
+          .placeBet(true, market.betLimits.minBetAmount.subn(1))
+          .accounts({
+            arenaState: arenaStatePda,
+            market: marketPda,
+            collateral: swtchCollateralPda,
+            userAccount: user1AccountPda,
+            position: positionPda,
+            user: user1.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([user1])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("BetTooSmall");
+      }
+    });
+
+    it.skip("should add to both sides of an existing position", async () => {
+      await program.methods
+        .placeBet(false, new anchor.BN(BET_AMOUNT)) // false = DOWN
+        .accounts({
+          arenaState: arenaStatePda,
+          market: marketPda,
+          collateral: swtchCollateralPda,
+          userAccount: user1AccountPda,
+          position: positionPda,
+          user: user1.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user1])
+        .rpc();
+
+      const position = await program.account.position.fetch(positionPda);
+      expect(position.upAmount.toNumber()).to.equal(BET_AMOUNT);
+      expect(position.downAmount.toNumber()).to.equal(BET_AMOUNT);
+      expect(position.amount.toNumber()).to.equal(2 * BET_AMOUNT);
+    });
+
+    it.skip("should reduce a position before betting closes", async () => {
+      const reduceBy = BET_AMOUNT / 2;
+
+      await program.methods
+        .reducePosition({ down: {} }, new anchor.BN(reduceBy))
+        .accounts({
+          arenaState: arenaStatePda,
+          market: marketPda,
+          collateral: swtchCollateralPda,
+          userAccount: user1AccountPda,
+          position: positionPda,
+          user: user1.publicKey,
+        })
+        .signers([user1])
+        .rpc();
+
+      const position = await program.account.position.fetch(positionPda);
+      expect(position.downAmount.toNumber()).to.equal(BET_AMOUNT - reduceBy);
+
+      const market = await program.account.market.fetch(marketPda);
+      expect(market.totalDownPool.toNumber()).to.equal(BET_AMOUNT - reduceBy);
+    });
   });
 
-  describe("admin functions", () => {
-    it("should update fee", async () => {
-      const newFee = 300; // 3%
-      
+  describe("cancel_market (requires active market)", () => {
+    it.skip("should cancel a market and refund stakes", async () => {
+      // This test requires the threshold market created above
+      const [thresholdMarketPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("market"), new anchor.BN(1).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
       await program.methods
-        .updateFee(newFee)
+        .cancelMarket({ oracleDeprecated: {} })
         .accounts({
           arenaState: arenaStatePda,
-          authority: provider.wallet.publicKey,
+          roleRegistry: roleRegistryPda,
+          market: thresholdMarketPda,
+          challengerAccount: null,
+          operator: provider.wallet.publicKey,
         })
         .rpc();
 
-      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
-      expect(arenaState.protocolFeeBps).to.equal(newFee);
+      const market = await program.account.market.fetch(thresholdMarketPda);
+      expect(market.status).to.deep.equal({ cancelled: {} });
+      expect(market.cancelReason).to.deep.equal({ oracleDeprecated: {} });
+      expect(market.outcome).to.deep.equal({ void: {} });
     });
 
-    it("should reject fee update from non-authority", async () => {
+    it.skip("should reject cancel from non-operator", async () => {
       try {
         await program.methods
-          .updateFee(200)
+          .cancelMarket({ other: {} })
           .accounts({
             arenaState: arenaStatePda,
-            authority: user1.publicKey, // Not the authority
+            roleRegistry: roleRegistryPda,
+            market: marketPda,
+            challengerAccount: null,
+            operator: user1.publicKey,
           })
           .signers([user1])
           .rpc();
-        
+
         expect.fail("Should have thrown error");
       } catch (err) {
         expect(err.message).to.include("Unauthorized");
       }
     });
+  });
+
+  describe("disputes (requires resolved market)", () => {
+    it.skip("should challenge a resolution with a bond", async () => {
+      // This test requires the market above to be resolved UP
+      const before = await program.account.userAccount.fetch(user2AccountPda);
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
 
-    it("should transfer authority", async () => {
-      const newAuthority = Keypair.generate();
-      
       await program.methods
-        .transferAuthority(newAuthority.publicKey)
+        .challengeResolution({ down: {} })
         .accounts({
           arenaState: arenaStatePda,
-          authority: provider.wallet.publicKey,
+          market: marketPda,
+          userAccount: user2AccountPda,
+          challenger: user2.publicKey,
         })
+        .signers([user2])
         .rpc();
 
-      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
-      expect(arenaState.authority.toString()).to.equal(
-        newAuthority.publicKey.toString()
+      const market = await program.account.market.fetch(marketPda);
+      expect(market.status).to.deep.equal({ disputed: {} });
+      expect(market.challenger.toString()).to.equal(user2.publicKey.toString());
+
+      const after = await program.account.userAccount.fetch(user2AccountPda);
+      expect(before.balance.sub(after.balance).toString()).to.equal(
+        arenaState.disputeBond.toString()
+      );
+    });
+  });
+
+  describe("close_market (requires active market)", () => {
+    it.skip("should reject closing a market that is not final", async () => {
+      try {
+        await program.methods
+          .closeMarket()
+          .accounts({
+            arenaState: arenaStatePda,
+            market: marketPda,
+            collateral: swtchCollateralPda,
+            samples: null,
+            rentReceiver: provider.wallet.publicKey,
+          })
+          .rpc();
+
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.message).to.include("MarketNotFinal");
+      }
+    });
+  });
+
+  describe("sweep_fees", () => {
+    it("should reject sweep with no escrowed fees", async () => {
+      try {
+        await program.methods
+          .sweepFees()
+          .accounts({
+            arenaState: arenaStatePda,
+            collateral: swtchCollateralPda,
+            mint: swtchMint,
+            vault: vaultPda,
+            treasury: treasuryTokenAccount,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.message).to.include("NoFeesToSweep");
+      }
+    });
+  });
+
+  describe("admin functions", () => {
+    it("should add a collateral mint with its own vault", async () => {
+      const usdcMint = await createMint(
+        provider.connection,
+        (provider.wallet as anchor.Wallet).payer,
+        provider.wallet.publicKey,
+        null,
+        6
+      );
+      const [usdcCollateralPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("collateral"), usdcMint.toBuffer()],
+        program.programId
+      );
+      const [usdcVaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), usdcMint.toBuffer()],
+        program.programId
       );
 
-      // Transfer back for other tests
       await program.methods
-        .transferAuthority(provider.wallet.publicKey)
+        .addCollateral()
         .accounts({
           arenaState: arenaStatePda,
-          authority: newAuthority.publicKey,
+          collateral: usdcCollateralPda,
+          vault: usdcVaultPda,
+          mint: usdcMint,
+          authority: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
         })
-        .signers([newAuthority])
         .rpc();
+
+      let collateral = await program.account.collateralConfig.fetch(usdcCollateralPda);
+      expect(collateral.mint.toString()).to.equal(usdcMint.toString());
+      expect(collateral.enabled).to.equal(true);
+
+      await program.methods
+        .setCollateralEnabled(false)
+        .accounts({
+          arenaState: arenaStatePda,
+          collateral: usdcCollateralPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      collateral = await program.account.collateralConfig.fetch(usdcCollateralPda);
+      expect(collateral.enabled).to.equal(false);
+    });
+
+    it("should update and remove an approved feed", async () => {
+      const feed = Keypair.generate().publicKey;
+      const [feedPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("approved_feed"), feed.toBuffer()],
+        program.programId
+      );
+      const params = {
+        oracleKind: { pullFeed: {} },
+        oracleQueue: Keypair.generate().publicKey,
+        minOracleResults: 3,
+        label: "ETH/USD",
+        category: "crypto",
+        requireLocked: false,
+      };
+
+      await program.methods
+        .approveFeed(params)
+        .accounts({
+          arenaState: arenaStatePda,
+          approvedFeed: feedPda,
+          oracleFeed: feed,
+          authority: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      await program.methods
+        .updateFeed({ ...params, requireLocked: true })
+        .accounts({
+          arenaState: arenaStatePda,
+          approvedFeed: feedPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const approvedFeed = await program.account.approvedFeed.fetch(feedPda);
+      expect(approvedFeed.minOracleResults).to.equal(3);
+      expect(approvedFeed.requireLocked).to.equal(true);
+
+      try {
+        await program.methods
+          .updateFeed({ ...params, minOracleResults: 0 })
+          .accounts({
+            arenaState: arenaStatePda,
+            approvedFeed: feedPda,
+            authority: provider.wallet.publicKey,
+          })
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidFeedApproval");
+      }
+
+      await program.methods
+        .removeFeed()
+        .accounts({
+          arenaState: arenaStatePda,
+          approvedFeed: feedPda,
+          authority: provider.wallet.publicKey,
+          rentReceiver: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(feedPda)).to.equal(null);
+    });
+
+    it("should reject feed approvals from non-authority", async () => {
+      const feed = Keypair.generate().publicKey;
+      const [feedPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("approved_feed"), feed.toBuffer()],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .approveFeed({
+            oracleKind: { pullFeed: {} },
+            oracleQueue: Keypair.generate().publicKey,
+            minOracleResults: 1,
+            label: "SOL/USD",
+            category: "crypto",
+            requireLocked: false,
+          })
+          .accounts({
+            arenaState: arenaStatePda,
+            approvedFeed: feedPda,
+            oracleFeed: feed,
+            authority: user1.publicKey,
+            payer: user1.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([user1])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+    });
+
+    it("should add a Token-2022 collateral mint", async () => {
+      const mint2022 = await createMint(
+        provider.connection,
+        (provider.wallet as anchor.Wallet).payer,
+        provider.wallet.publicKey,
+        null,
+        6,
+        Keypair.generate(),
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      const [collateralPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("collateral"), mint2022.toBuffer()],
+        program.programId
+      );
+      const [mintVaultPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("vault"), mint2022.toBuffer()],
+        program.programId
+      );
+
+      await program.methods
+        .addCollateral()
+        .accounts({
+          arenaState: arenaStatePda,
+          collateral: collateralPda,
+          vault: mintVaultPda,
+          mint: mint2022,
+          authority: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .rpc();
+
+      const vault = await getAccount(
+        provider.connection,
+        mintVaultPda,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      expect(vault.owner.toString()).to.equal(arenaStatePda.toString());
+    });
+
+    const pendingChangePda = (changeId: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("pending_change"), changeId.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    it("should queue a fee change behind the timelock", async () => {
+      const newFee = 300; // 3%
+      let arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      const oldFee = arenaState.protocolFeeBps;
+      const pendingChange = pendingChangePda(arenaState.totalChanges);
+
+      await program.methods
+        .queueChange({ protocolFee: { feeBps: newFee } })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          pendingChange,
+          proposer: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const queued = await program.account.pendingChange.fetch(pendingChange);
+      expect(queued.change.protocolFee.feeBps).to.equal(newFee);
+      expect(queued.executableAt.toNumber()).to.be.greaterThan(
+        Math.floor(Date.now() / 1000) + 86_400
+      );
+
+      // Not applied until the delay has passed
+      arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      expect(arenaState.protocolFeeBps).to.equal(oldFee);
+
+      try {
+        await program.methods
+          .executeChange()
+          .accounts({
+            arenaState: arenaStatePda,
+            pendingChange,
+            payer: provider.wallet.publicKey,
+          })
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.message).to.include("TimelockNotElapsed");
+      }
+
+      await program.methods
+        .cancelChange()
+        .accounts({
+          arenaState: arenaStatePda,
+          pendingChange,
+          payer: provider.wallet.publicKey,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(pendingChange)).to.be.null;
+    });
+
+    it("should reject fee change from non-authority", async () => {
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+
+      try {
+        await program.methods
+          .queueChange({ protocolFee: { feeBps: 200 } })
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            pendingChange: pendingChangePda(arenaState.totalChanges),
+            proposer: user1.publicKey, // Not the authority
+            payer: user1.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([user1])
+          .rpc();
+        
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.message).to.include("Unauthorized");
+      }
+    });
+
+    it("should queue an oracle policy change", async () => {
+      const oraclePolicy = {
+        maxStalenessSecs: new anchor.BN(120),
+        maxStdDeviation: new anchor.BN(0),
+        minOracleResults: 3,
+        resolutionGraceSecs: new anchor.BN(1800),
+      };
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      const pendingChange = pendingChangePda(arenaState.totalChanges);
+
+      await program.methods
+        .queueChange({ oraclePolicy: { oraclePolicy } })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          pendingChange,
+          proposer: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const queued = await program.account.pendingChange.fetch(pendingChange);
+      expect(queued.change.oraclePolicy.oraclePolicy.minOracleResults).to.equal(3);
+      expect(
+        queued.change.oraclePolicy.oraclePolicy.resolutionGraceSecs.toNumber()
+      ).to.equal(1800);
+    });
+
+    it("should queue a referral fee change", async () => {
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      const pendingChange = pendingChangePda(arenaState.totalChanges);
+
+      await program.methods
+        .queueChange({ referralFee: { feeBps: 2_000 } })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          pendingChange,
+          proposer: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const queued = await program.account.pendingChange.fetch(pendingChange);
+      expect(queued.change.referralFee.feeBps).to.equal(2_000);
+
+      try {
+        await program.methods
+          .queueChange({ referralFee: { feeBps: 6_000 } })
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            pendingChange: pendingChangePda(arenaState.totalChanges.addn(1)),
+            proposer: provider.wallet.publicKey,
+            payer: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidFeePercentage");
+      }
+    });
+
+    it("should reject oracle policy without responding oracles", async () => {
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+
+      try {
+        await program.methods
+          .queueChange({
+            oraclePolicy: {
+              oraclePolicy: {
+                maxStalenessSecs: new anchor.BN(120),
+                maxStdDeviation: new anchor.BN(0),
+                minOracleResults: 0,
+                resolutionGraceSecs: new anchor.BN(1800),
+              },
+            },
+          })
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            pendingChange: pendingChangePda(arenaState.totalChanges),
+            proposer: provider.wallet.publicKey,
+            payer: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.message).to.include("InvalidOraclePolicy");
+      }
+    });
+
+    it("should queue bet limits with exposure caps", async () => {
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      const pendingChange = pendingChangePda(arenaState.totalChanges);
+
+      await program.methods
+        .queueChange({
+          betLimits: {
+            betLimits: {
+              minBetAmount: new anchor.BN(1_000_000),
+              maxBetAmount: new anchor.BN(5_000_000_000),
+              maxPositionAmount: new anchor.BN(10_000_000_000),
+            },
+            maxOpenExposure: new anchor.BN(50_000_000_000),
+          },
+        })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          pendingChange,
+          proposer: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const queued = await program.account.pendingChange.fetch(pendingChange);
+      expect(queued.change.betLimits.maxOpenExposure.toNumber()).to.equal(50_000_000_000);
+
+      await program.methods
+        .cancelChange()
+        .accounts({
+          arenaState: arenaStatePda,
+          pendingChange,
+          payer: provider.wallet.publicKey,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+    });
+
+    it("should reject bet limits with min above max", async () => {
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+
+      try {
+        await program.methods
+          .queueChange({
+            betLimits: {
+              betLimits: {
+                minBetAmount: new anchor.BN(10_000_000),
+                maxBetAmount: new anchor.BN(1_000_000),
+                maxPositionAmount: new anchor.BN(0),
+              },
+              maxOpenExposure: new anchor.BN(0),
+            },
+          })
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            pendingChange: pendingChangePda(arenaState.totalChanges),
+            proposer: provider.wallet.publicKey,
+            payer: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.message).to.include("InvalidBetLimits");
+      }
+    });
+
+    it("should queue a dispute config change", async () => {
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      const pendingChange = pendingChangePda(arenaState.totalChanges);
+
+      await program.methods
+        .queueChange({
+          disputeConfig: {
+            disputePeriodSecs: new anchor.BN(3600),
+            disputeBond: new anchor.BN(50_000_000),
+          },
+        })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          pendingChange,
+          proposer: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const queued = await program.account.pendingChange.fetch(pendingChange);
+      expect(queued.change.disputeConfig.disputePeriodSecs.toNumber()).to.equal(3600);
+      expect(queued.change.disputeConfig.disputeBond.toNumber()).to.equal(50_000_000);
+
+      // Not applied until the delay has passed
+      const unchanged = await program.account.arenaState.fetch(arenaStatePda);
+      expect(unchanged.disputePeriodSecs.toNumber()).to.equal(86_400);
+    });
+
+    it("should reject dispute period over 7 days", async () => {
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+
+      try {
+        await program.methods
+          .queueChange({
+            disputeConfig: {
+              disputePeriodSecs: new anchor.BN(8 * 86_400),
+              disputeBond: new anchor.BN(50_000_000),
+            },
+          })
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            pendingChange: pendingChangePda(arenaState.totalChanges),
+            proposer: provider.wallet.publicKey,
+            payer: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.message).to.include("InvalidDisputeConfig");
+      }
+    });
+
+    it("should reject a non-positive claim expiry", async () => {
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+
+      try {
+        await program.methods
+          .queueChange({ claimExpiry: { claimExpirySecs: new anchor.BN(0) } })
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            pendingChange: pendingChangePda(arenaState.totalChanges),
+            proposer: provider.wallet.publicKey,
+            payer: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.message).to.include("InvalidClaimExpiry");
+      }
+    });
+
+    it("should queue opening market creation with a creator bond and fee share", async () => {
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      const pendingChange = pendingChangePda(arenaState.totalChanges);
+
+      await program.methods
+        .queueChange({
+          creationConfig: {
+            openCreation: true,
+            creationBond: new anchor.BN(100_000_000),
+            creatorFeeBps: 2_000, // 20% of the fee
+          },
+        })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          pendingChange,
+          proposer: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const queued = await program.account.pendingChange.fetch(pendingChange);
+      expect(queued.change.creationConfig.openCreation).to.equal(true);
+      expect(queued.change.creationConfig.creationBond.toNumber()).to.equal(100_000_000);
+      expect(queued.change.creationConfig.creatorFeeBps).to.equal(2_000);
+
+      try {
+        await program.methods
+          .queueChange({
+            creationConfig: {
+              openCreation: true,
+              creationBond: new anchor.BN(100_000_000),
+              creatorFeeBps: 6_000,
+            },
+          })
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            pendingChange: pendingChangePda(arenaState.totalChanges.addn(1)),
+            proposer: provider.wallet.publicKey,
+            payer: provider.wallet.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("InvalidFeePercentage");
+      }
+    });
+
+    it("should grant and revoke roles", async () => {
+      const ROLE_FEE_ADMIN = 1 << 1;
+      const ROLE_PAUSER = 1 << 2;
+
+      await program.methods
+        .grantRole(user2.publicKey, ROLE_FEE_ADMIN | ROLE_PAUSER)
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      let roleRegistry = await program.account.roleRegistry.fetch(roleRegistryPda);
+      expect(roleRegistry.members).to.have.lengthOf(1);
+      expect(roleRegistry.members[0].roles).to.equal(ROLE_FEE_ADMIN | ROLE_PAUSER);
+
+      // A fee admin can queue fee changes without being the authority
+      let arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      await program.methods
+        .queueChange({ exitFee: { feeBps: 100 } })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          pendingChange: pendingChangePda(arenaState.totalChanges),
+          proposer: user2.publicKey,
+          payer: user2.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user2])
+        .rpc();
+
+      // ...but not other admin changes
+      arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      try {
+        await program.methods
+          .queueChange({ treasury: { treasury: user2.publicKey } })
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            pendingChange: pendingChangePda(arenaState.totalChanges),
+            proposer: user2.publicKey,
+            payer: user2.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([user2])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .revokeRole(user2.publicKey, ROLE_FEE_ADMIN)
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      try {
+        await program.methods
+          .queueChange({ exitFee: { feeBps: 200 } })
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            pendingChange: pendingChangePda(arenaState.totalChanges),
+            proposer: user2.publicKey,
+            payer: user2.publicKey,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([user2])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .revokeRole(user2.publicKey, ROLE_PAUSER)
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      roleRegistry = await program.account.roleRegistry.fetch(roleRegistryPda);
+      expect(roleRegistry.members).to.have.lengthOf(0);
+    });
+
+    it("should not let role holders grant roles", async () => {
+      try {
+        await program.methods
+          .grantRole(user1.publicKey, 1)
+          .accounts({
+            arenaState: arenaStatePda,
+            roleRegistry: roleRegistryPda,
+            authority: user1.publicKey,
+          })
+          .signers([user1])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+    });
+
+    it("should pause deposits without blocking withdrawals", async () => {
+      const PAUSE_DEPOSITS = 1 << 0;
+
+      await program.methods
+        .pause(PAUSE_DEPOSITS)
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      let arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      expect(arenaState.paused).to.equal(PAUSE_DEPOSITS);
+
+      try {
+        await program.methods
+          .deposit(new anchor.BN(1_000_000))
+          .accounts({
+            arenaState: arenaStatePda,
+            collateral: swtchCollateralPda,
+            mint: swtchMint,
+            vault: vaultPda,
+            userAccount: user1AccountPda,
+            userTokenAccount: user1TokenAccount,
+            referral: user1ReferralPda,
+            referrerReferral: null,
+            user: user1.publicKey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([user1])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("Paused");
+      }
+
+      await program.methods
+        .withdraw(new anchor.BN(1_000_000))
+        .accounts({
+          arenaState: arenaStatePda,
+          mint: swtchMint,
+          vault: vaultPda,
+          userAccount: user1AccountPda,
+          userTokenAccount: user1TokenAccount,
+          user: user1.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user1])
+        .rpc();
+
+      await program.methods
+        .unpause(PAUSE_DEPOSITS)
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      expect(arenaState.paused).to.equal(0);
+    });
+
+    it("should transfer authority in two steps", async () => {
+      const newAuthority = Keypair.generate();
+
+      await program.methods
+        .proposeAuthority(newAuthority.publicKey)
+        .accounts({
+          arenaState: arenaStatePda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      let arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      expect(arenaState.authority.toString()).to.equal(
+        provider.wallet.publicKey.toString()
+      );
+      expect(arenaState.pendingAuthority.toString()).to.equal(
+        newAuthority.publicKey.toString()
+      );
+
+      await program.methods
+        .acceptAuthority()
+        .accounts({
+          arenaState: arenaStatePda,
+          newAuthority: newAuthority.publicKey,
+        })
+        .signers([newAuthority])
+        .rpc();
+
+      arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      expect(arenaState.authority.toString()).to.equal(
+        newAuthority.publicKey.toString()
+      );
+      expect(arenaState.pendingAuthority.toString()).to.equal(
+        PublicKey.default.toString()
+      );
+
+      // Transfer back for other tests
+      await program.methods
+        .proposeAuthority(provider.wallet.publicKey)
+        .accounts({
+          arenaState: arenaStatePda,
+          authority: newAuthority.publicKey,
+        })
+        .signers([newAuthority])
+        .rpc();
+      await program.methods
+        .acceptAuthority()
+        .accounts({
+          arenaState: arenaStatePda,
+          newAuthority: provider.wallet.publicKey,
+        })
+        .rpc();
+    });
+
+    it("should reject accepting authority with the wrong key", async () => {
+      const newAuthority = Keypair.generate();
+
+      await program.methods
+        .proposeAuthority(newAuthority.publicKey)
+        .accounts({
+          arenaState: arenaStatePda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      try {
+        await program.methods
+          .acceptAuthority()
+          .accounts({
+            arenaState: arenaStatePda,
+            newAuthority: user1.publicKey,
+          })
+          .signers([user1])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .cancelAuthorityTransfer()
+        .accounts({
+          arenaState: arenaStatePda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      expect(arenaState.pendingAuthority.toString()).to.equal(
+        PublicKey.default.toString()
+      );
+    });
+
+    it("should queue a treasury change for the new wallet to accept", async () => {
+      const newTreasury = Keypair.generate();
+      const arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      const pendingChange = pendingChangePda(arenaState.totalChanges);
+
+      await program.methods
+        .queueChange({ treasury: { treasury: newTreasury.publicKey } })
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          pendingChange,
+          proposer: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      // Nothing to accept until the change is executed
+      try {
+        await program.methods
+          .acceptTreasury()
+          .accounts({
+            arenaState: arenaStatePda,
+            newTreasury: newTreasury.publicKey,
+          })
+          .signers([newTreasury])
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("Unauthorized");
+      }
+
+      await program.methods
+        .cancelChange()
+        .accounts({
+          arenaState: arenaStatePda,
+          pendingChange,
+          payer: provider.wallet.publicKey,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+    });
+  });
+
+  describe("admin council", () => {
+    const [councilPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("council")],
+      program.programId
+    );
+
+    const councilActionPda = (actionId: anchor.BN) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("council_action"), actionId.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    // The council PDA is signed for by the program, not the transaction
+    const actionAccounts = (ix: anchor.web3.TransactionInstruction) =>
+      ix.keys.map((meta) => ({
+        pubkey: meta.pubkey,
+        isSigner: meta.isSigner && !meta.pubkey.equals(councilPda),
+        isWritable: meta.isWritable,
+      }));
+
+    const proposeAction = async (ix: anchor.web3.TransactionInstruction) => {
+      const council = await program.account.adminCouncil.fetch(councilPda);
+      const action = councilActionPda(council.totalActions);
+
+      await program.methods
+        .proposeCouncilAction(
+          ix.keys.map((meta) => ({
+            pubkey: meta.pubkey,
+            isSigner: meta.isSigner,
+            isWritable: meta.isWritable,
+          })),
+          ix.data
+        )
+        .accounts({
+          council: councilPda,
+          action,
+          proposer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      return action;
+    };
+
+    const runAction = async (ix: anchor.web3.TransactionInstruction) => {
+      const action = await proposeAction(ix);
+
+      await program.methods
+        .approveCouncilAction()
+        .accounts({
+          council: councilPda,
+          action,
+          member: user1.publicKey,
+        })
+        .signers([user1])
+        .rpc();
+
+      await program.methods
+        .executeCouncilAction()
+        .accounts({
+          council: councilPda,
+          action,
+          proposer: provider.wallet.publicKey,
+        })
+        .remainingAccounts(actionAccounts(ix))
+        .rpc();
+    };
+
+    it("should create a 2-of-2 council", async () => {
+      await program.methods
+        .createCouncil([provider.wallet.publicKey, user1.publicKey], 2)
+        .accounts({
+          arenaState: arenaStatePda,
+          council: councilPda,
+          authority: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      const council = await program.account.adminCouncil.fetch(councilPda);
+      expect(council.members).to.have.lengthOf(2);
+      expect(council.threshold).to.equal(2);
+    });
+
+    it("should not execute an action below the threshold", async () => {
+      const ix = await program.methods
+        .pause(1)
+        .accounts({
+          arenaState: arenaStatePda,
+          roleRegistry: roleRegistryPda,
+          authority: councilPda,
+        })
+        .instruction();
+      const action = await proposeAction(ix);
+
+      try {
+        await program.methods
+          .executeCouncilAction()
+          .accounts({
+            council: councilPda,
+            action,
+            proposer: provider.wallet.publicKey,
+          })
+          .remainingAccounts(actionAccounts(ix))
+          .rpc();
+        expect.fail("Should have thrown error");
+      } catch (err) {
+        expect(err.toString()).to.include("CouncilThresholdNotMet");
+      }
+
+      await program.methods
+        .cancelCouncilAction()
+        .accounts({
+          action,
+          proposer: provider.wallet.publicKey,
+        })
+        .rpc();
+    });
+
+    it("should take over and hand back the authority", async () => {
+      await program.methods
+        .proposeAuthority(councilPda)
+        .accounts({
+          arenaState: arenaStatePda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      await runAction(
+        await program.methods
+          .acceptAuthority()
+          .accounts({
+            arenaState: arenaStatePda,
+            newAuthority: councilPda,
+          })
+          .instruction()
+      );
+
+      let arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      expect(arenaState.authority.toString()).to.equal(councilPda.toString());
+
+      // The council now signs admin instructions
+      await runAction(
+        await program.methods
+          .proposeAuthority(provider.wallet.publicKey)
+          .accounts({
+            arenaState: arenaStatePda,
+            authority: councilPda,
+          })
+          .instruction()
+      );
+
+      await program.methods
+        .acceptAuthority()
+        .accounts({
+          arenaState: arenaStatePda,
+          newAuthority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+      arenaState = await program.account.arenaState.fetch(arenaStatePda);
+      expect(arenaState.authority.toString()).to.equal(
+        provider.wallet.publicKey.toString()
+      );
     });
   });
 });